@@ -0,0 +1,351 @@
+// A minimal HTTP server, not a general web server: it exists solely so a
+// LAN-only Plex server's token-bearing artwork URL (see
+// `PlexServer::plex_artwork_url`) can be fetched server-side and re-served
+// from an externally reachable address, without Discord (or anyone sniffing
+// the embed) ever seeing the Plex token. Also serves locally bundled
+// offline fallback artwork (`Config::offline_artwork_dir`) the same way,
+// since that's just as unreachable from Discord as a LAN Plex server is.
+// Serves exactly two routes, `/art` and `/offline`, and nothing else. `/art`
+// only proxies targets matching an `AllowedOrigins` entry, so the bound
+// address can't be used to fetch arbitrary URLs.
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use log::{debug, warn};
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use reqwest::{Client, Url};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+// The `scheme://host:port` origins of every connection a monitored server
+// was reached through, kept up to date as servers are (re)discovered. `/art`
+// only proxies requests whose target resolves to one of these — otherwise
+// this would be an open relay for whatever URL a caller to the bound address
+// cares to send.
+pub type AllowedOrigins = Arc<StdMutex<HashSet<String>>>;
+
+// Rewrites a direct (token-bearing) artwork URL into one pointing at this
+// proxy, for handing to Discord instead. `base_url` is the externally
+// reachable address configured as `Config::art_proxy_public_base_url`.
+pub fn public_url(base_url: &str, target: &str) -> String {
+    let base = base_url.trim_end_matches('/');
+    let encoded = utf8_percent_encode(target, NON_ALPHANUMERIC);
+    format!("{base}/art?url={encoded}")
+}
+
+// Points at a file in `Config::offline_artwork_dir`, for handing to Discord
+// as a large_image URL instead of the generic `plex_logo` fallback.
+pub fn offline_url(base_url: &str, filename: &str) -> String {
+    let base = base_url.trim_end_matches('/');
+    let encoded = utf8_percent_encode(filename, NON_ALPHANUMERIC);
+    format!("{base}/offline?name={encoded}")
+}
+
+// Listens on `bind_addr` and serves `GET /art?url=<encoded target>` by
+// proxying it through `client` (rejecting targets outside
+// `allowed_origins`), and `GET /offline?name=<filename>` by reading
+// `filename` out of `offline_artwork_dir`. Runs until the process exits;
+// callers spawn this as a background task.
+pub async fn serve(
+    bind_addr: String,
+    client: Client,
+    offline_artwork_dir: Option<PathBuf>,
+    allowed_origins: AllowedOrigins,
+) {
+    let listener = match TcpListener::bind(&bind_addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Art proxy failed to bind {}: {}", bind_addr, e);
+            return;
+        }
+    };
+    debug!("Art proxy listening on {}", bind_addr);
+
+    loop {
+        let (socket, _) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("Art proxy accept failed: {}", e);
+                continue;
+            }
+        };
+        let client = client.clone();
+        let offline_artwork_dir = offline_artwork_dir.clone();
+        let allowed_origins = Arc::clone(&allowed_origins);
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(
+                socket,
+                &client,
+                offline_artwork_dir.as_deref(),
+                &allowed_origins,
+            )
+            .await
+            {
+                debug!("Art proxy connection error: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle_connection(
+    socket: tokio::net::TcpStream,
+    client: &Client,
+    offline_artwork_dir: Option<&Path>,
+    allowed_origins: &AllowedOrigins,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(socket);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+
+    // Drain (and ignore) headers; this proxy doesn't need any of them.
+    loop {
+        let mut header_line = String::new();
+        if reader.read_line(&mut header_line).await? == 0 || header_line == "\r\n" {
+            break;
+        }
+    }
+
+    let mut socket = reader.into_inner();
+    match Route::from_request_line(&request_line) {
+        Some(Route::Art(target)) => respond_art(socket, target, client, allowed_origins).await,
+        Some(Route::Offline(name)) => respond_offline(socket, name, offline_artwork_dir).await,
+        None => write_response(&mut socket, 400, "text/plain", b"unknown route").await,
+    }
+}
+
+#[derive(Debug)]
+enum Route {
+    Art(String),
+    Offline(String),
+}
+
+impl Route {
+    // Parses a request line like `GET /art?url=<encoded> HTTP/1.1` or
+    // `GET /offline?name=<encoded> HTTP/1.1` into its query param,
+    // percent-decoded.
+    fn from_request_line(line: &str) -> Option<Self> {
+        let path = line.split_whitespace().nth(1)?;
+        let (route, query) = path.split_once('?')?;
+        let decode = |param: &str| {
+            query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix(param))
+                .and_then(|v| percent_encoding::percent_decode_str(v).decode_utf8().ok())
+                .map(String::from)
+        };
+        match route {
+            "/art" => decode("url=").map(Route::Art),
+            "/offline" => decode("name=").map(Route::Offline),
+            _ => None,
+        }
+    }
+}
+
+async fn respond_art(
+    mut socket: tokio::net::TcpStream,
+    target: String,
+    client: &Client,
+    allowed_origins: &AllowedOrigins,
+) -> std::io::Result<()> {
+    if !is_allowed_target(&target, allowed_origins) {
+        warn!(
+            "Art proxy refused a target outside the monitored servers: {}",
+            crate::redact::redact(&target)
+        );
+        return write_response(&mut socket, 403, "text/plain", b"target not allowed").await;
+    }
+
+    let upstream = client.get(&target).send().await;
+    match upstream {
+        Ok(resp) if resp.status().is_success() => {
+            let content_type = resp
+                .headers()
+                .get("content-type")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("application/octet-stream")
+                .to_string();
+            let body = resp.bytes().await.unwrap_or_default().to_vec();
+            write_response(&mut socket, 200, &content_type, &body).await
+        }
+        _ => write_response(&mut socket, 502, "text/plain", b"upstream fetch failed").await,
+    }
+}
+
+// `target` is only safe to fetch if it resolves to the same scheme+host+port
+// as one of the connections a monitored server was actually reached
+// through — otherwise `/art` is an open relay for any URL a caller to the
+// bound address cares to send, including internal/cloud-metadata addresses.
+fn is_allowed_target(target: &str, allowed_origins: &AllowedOrigins) -> bool {
+    let Ok(url) = Url::parse(target) else {
+        return false;
+    };
+    allowed_origins
+        .lock()
+        .unwrap()
+        .contains(&url.origin().ascii_serialization())
+}
+
+async fn respond_offline(
+    mut socket: tokio::net::TcpStream,
+    name: String,
+    offline_artwork_dir: Option<&Path>,
+) -> std::io::Result<()> {
+    let Some(path) = offline_artwork_dir.and_then(|dir| resolve_offline_path(dir, &name)) else {
+        return write_response(&mut socket, 404, "text/plain", b"not found").await;
+    };
+    match tokio::fs::read(&path).await {
+        Ok(body) => write_response(&mut socket, 200, content_type_for(&path), &body).await,
+        Err(_) => write_response(&mut socket, 404, "text/plain", b"not found").await,
+    }
+}
+
+// Joins `name` onto `dir`, rejecting anything that isn't a plain filename
+// (no path separators or `..`) so `/offline?name=` can't be used to read
+// arbitrary files off disk.
+fn resolve_offline_path(dir: &Path, name: &str) -> Option<PathBuf> {
+    let file_name = Path::new(name).file_name()?;
+    if file_name != std::ffi::OsStr::new(name) {
+        return None;
+    }
+    Some(dir.join(file_name))
+}
+
+fn content_type_for(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        Some("gif") => "image/gif",
+        _ => "application/octet-stream",
+    }
+}
+
+async fn write_response(
+    socket: &mut tokio::net::TcpStream,
+    status: u16,
+    content_type: &str,
+    body: &[u8],
+) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        _ => "Bad Gateway",
+    };
+    let header = format!(
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: {content_type}\r\nContent-Length: {len}\r\nConnection: close\r\n\r\n",
+        len = body.len()
+    );
+    socket.write_all(header.as_bytes()).await?;
+    socket.write_all(body).await?;
+    socket.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn public_url_encodes_the_target_and_trims_a_trailing_slash_on_base() {
+        let url = public_url(
+            "https://my-tunnel.example.com/",
+            "https://plex.local/photo/:/transcode?url=x&X-Plex-Token=secret",
+        );
+        assert_eq!(
+            url,
+            "https://my-tunnel.example.com/art?url=https%3A%2F%2Fplex%2Elocal%2Fphoto%2F%3A%2Ftranscode%3Furl%3Dx%26X%2DPlex%2DToken%3Dsecret"
+        );
+    }
+
+    #[test]
+    fn offline_url_encodes_the_filename_and_trims_a_trailing_slash_on_base() {
+        let url = offline_url("https://my-tunnel.example.com/", "movie poster.png");
+        assert_eq!(
+            url,
+            "https://my-tunnel.example.com/offline?name=movie%20poster%2Epng"
+        );
+    }
+
+    #[test]
+    fn route_parses_an_art_request() {
+        match Route::from_request_line("GET /art?url=https%3A%2F%2Fexample.com%2Fa HTTP/1.1\r\n") {
+            Some(Route::Art(target)) => assert_eq!(target, "https://example.com/a"),
+            other => panic!("expected Route::Art, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn route_parses_an_offline_request() {
+        match Route::from_request_line("GET /offline?name=movie.png HTTP/1.1\r\n") {
+            Some(Route::Offline(name)) => assert_eq!(name, "movie.png"),
+            other => panic!("expected Route::Offline, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn route_is_none_for_an_unknown_path() {
+        assert!(Route::from_request_line("GET /other?url=x HTTP/1.1\r\n").is_none());
+    }
+
+    #[test]
+    fn route_is_none_without_a_query_param() {
+        assert!(Route::from_request_line("GET /art HTTP/1.1\r\n").is_none());
+    }
+
+    #[test]
+    fn resolve_offline_path_joins_a_plain_filename() {
+        let dir = Path::new("/tmp/offline-art");
+        assert_eq!(
+            resolve_offline_path(dir, "movie.png"),
+            Some(dir.join("movie.png"))
+        );
+    }
+
+    #[test]
+    fn resolve_offline_path_rejects_path_traversal() {
+        let dir = Path::new("/tmp/offline-art");
+        assert_eq!(resolve_offline_path(dir, "../secret.png"), None);
+        assert_eq!(resolve_offline_path(dir, "sub/dir.png"), None);
+    }
+
+    fn allowed(origins: &[&str]) -> AllowedOrigins {
+        Arc::new(StdMutex::new(
+            origins.iter().map(|o| o.to_string()).collect(),
+        ))
+    }
+
+    #[test]
+    fn is_allowed_target_accepts_a_matching_origin() {
+        let origins = allowed(&["https://192.168.1.50:32400"]);
+        assert!(is_allowed_target(
+            "https://192.168.1.50:32400/photo/:/transcode?url=x",
+            &origins
+        ));
+    }
+
+    #[test]
+    fn is_allowed_target_rejects_an_unlisted_host() {
+        let origins = allowed(&["https://192.168.1.50:32400"]);
+        assert!(!is_allowed_target(
+            "http://169.254.169.254/latest/meta-data/",
+            &origins
+        ));
+    }
+
+    #[test]
+    fn is_allowed_target_rejects_a_scheme_mismatch_on_an_otherwise_listed_host() {
+        let origins = allowed(&["https://192.168.1.50:32400"]);
+        assert!(!is_allowed_target(
+            "http://192.168.1.50:32400/photo/:/transcode?url=x",
+            &origins
+        ));
+    }
+
+    #[test]
+    fn is_allowed_target_rejects_an_unparseable_url() {
+        let origins = allowed(&["https://192.168.1.50:32400"]);
+        assert!(!is_allowed_target("not a url", &origins));
+    }
+}
@@ -1,73 +1,443 @@
+use crate::discord::{ActivityType, TimestampMode};
+use crate::metadata::{LargeImageStyle, TvArtworkLevel};
+use crate::plex_server::NotificationTransport;
+use crate::presence::{AirDateFormat, ButtonSource};
+use log::LevelFilter;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// Bumped whenever a config layout change needs more than
+// `#[serde(default)]` to upgrade safely, e.g. a rename or a field splitting
+// into several. See `migrate`.
+const CONFIG_VERSION: u32 = 1;
+
+const DEFAULT_USER_AGENT: &str = "PresenceForPlex/1.0";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Config {
+    // The on-disk layout version, stamped to `CONFIG_VERSION` by `migrate`
+    // on load. A config from before this field existed would get
+    // `CONFIG_VERSION` here through `#[serde(default)]`, so `load` reads the
+    // key off the raw document instead and treats a missing key as `0`:
+    // pre-versioning.
+    pub version: u32,
     pub discord_client_id: String,
+    // Overrides discord_client_id for anime (genre "Anime"), so a separate
+    // Discord application with its own curated art keys can be used. None
+    // keeps using discord_client_id for everything.
+    pub anime_discord_client_id: Option<String>,
+    // Genres whose name contains any of these (case-insensitive substrings)
+    // mark content as anime, e.g. a custom "Anime (Subbed)" genre/collection.
+    pub anime_genre_keywords: Vec<String>,
     pub show_buttons: bool,
-    pub show_progress: bool,
+    // Which external-service buttons to show and in what order, capped at
+    // two (Discord's limit). A source whose id isn't available (e.g. no
+    // TMDB id) is skipped rather than leaving a gap.
+    pub button_sources: Vec<ButtonSource>,
+    // Which half (if any) of the start/end timestamp pair Discord renders:
+    // an elapsed clock, a remaining-time countdown, both, or neither.
+    pub timestamp_mode: TimestampMode,
+    // While paused/buffering, DiscordClient freezes the bar by pushing the
+    // timestamp ~9999 hours into the future rather than by omitting it,
+    // since some Discord clients show a distracting placeholder instead of
+    // a progress bar when there's no timestamp at all. A few Discord
+    // versions instead render that huge offset as a weird giant elapsed
+    // time, so this lets those users opt into omitting timestamps entirely
+    // while paused/buffering instead.
+    pub freeze_paused_timestamp: bool,
+    // Global default for whether presence shows fetched artwork or the
+    // plain app logo. The per-media-type flags below override this when
+    // set, e.g. to keep movie/TV posters but fall back to the logo for
+    // flaky music album art.
     pub show_artwork: bool,
+    pub show_artwork_movies: Option<bool>,
+    pub show_artwork_tv: Option<bool>,
+    pub show_artwork_music: Option<bool>,
 
     pub plex_token: Option<String>,
     pub enable_movies: bool,
     pub enable_tv_shows: bool,
     pub enable_music: bool,
+    // Plex's "clip" type, e.g. music videos that aren't tied to a movie or
+    // TV library. Trailers/extras are still filtered separately by
+    // `ignore_extras`.
+    pub enable_clips: bool,
 
     pub tmdb_token: Option<String>,
 
+    // TMDB image size variant to request artwork at: w342, w500, w780, or
+    // original. Larger sizes look sharper on high-DPI displays but take
+    // longer to fetch. Falls back to w500 if not one of those values.
+    pub tmdb_image_size: String,
+
+    // Which TMDB image to use for TV episodes: the series poster, the
+    // episode's season poster, or (rarely populated) the episode still.
+    pub tv_artwork_level: TvArtworkLevel,
+
+    // Which kind of TMDB image to prefer as the large image: the poster, a
+    // backdrop, or the transparent logo. Falls back to the poster when the
+    // preferred kind doesn't exist for an item.
+    pub large_image_style: LargeImageStyle,
+
+    // How the `{air_date}` placeholder renders: the raw "YYYY-MM-DD" Plex
+    // reports, or a localized "Apr 14, 2019" style string.
+    pub air_date_format: AirDateFormat,
+
+    // Maps a genre name (matched case-insensitively) to a custom Discord
+    // small-image asset key, e.g. "Horror" -> "horror_badge", shown as a
+    // badge overlay while playing. The first of the item's genres with an
+    // entry here wins. Empty by default.
+    pub genre_small_images: HashMap<String, String>,
+
     // Format templates
     pub tv_details: String,
     pub tv_state: String,
     pub tv_image_text: String,
+    // Overrides the tv_* templates for episodes detected as anime (MAL id
+    // present, or a genre matching anime_genre_keywords), e.g. to drop
+    // season numbering with "{show} · Episode {episode}". None falls back
+    // to the tv_* templates as usual.
+    pub anime_details: Option<String>,
+    pub anime_state: Option<String>,
+    pub anime_image_text: Option<String>,
     pub movie_details: String,
     pub movie_state: String,
     pub movie_image_text: String,
+    // Strips a trailing parenthetical suffix from movie titles before
+    // templating, e.g. "Blade Runner (Director's Cut)" -> "Blade Runner".
+    // The year is still available separately via {year}.
+    pub clean_movie_titles: bool,
+    // When an episode's title is blank (some poorly-scraped libraries leave
+    // it empty), render "Episode {episode}" instead of an empty `{title}`.
+    // Off by default since some users prefer the blank left as-is.
+    pub episode_title_fallback: bool,
+    // Caps every rendered details/state/large_image_text field at this many
+    // graphemes, appending an ellipsis when cut. Lets templates control
+    // overflow (e.g. a long {genres} list) before Discord's own, larger,
+    // hard field limit would otherwise cut it off mid-word. None leaves
+    // fields uncapped except by Discord's own limit.
+    pub template_max_len: Option<usize>,
+    // Discord rejects a `details`/`state` value that's empty or
+    // whitespace-only. When a template renders to nothing (e.g. {genres}
+    // for a movie with none), substitute this text instead of sending it
+    // blank. None leaves the field blank, which `DiscordClient::update`
+    // then simply omits rather than sending empty.
+    pub empty_field_placeholder: Option<String>,
     pub music_details: String,
     pub music_state: String,
     pub music_image_text: String,
+    // Swaps the music details/state lines so the artist leads, Spotify-style
+    pub music_artist_forward: bool,
+    pub clip_details: String,
+    pub clip_state: String,
+    pub clip_image_text: String,
+
+    // Overrides the Discord activity type per media type; None keeps the
+    // built-in default (Watching for movies/TV/clips, Listening for music).
+    pub movie_activity_type: Option<ActivityType>,
+    pub tv_activity_type: Option<ActivityType>,
+    pub music_activity_type: Option<ActivityType>,
+    pub clip_activity_type: Option<ActivityType>,
+    // Overrides the activity type while `{is_group}` is true (a Plex Watch
+    // Together session), taking priority over the per-media-type overrides
+    // above. None leaves group sessions using the normal resolution.
+    pub group_activity_type: Option<ActivityType>,
+
+    // One-flag preset for users who don't want to craft templates: forces
+    // the details line to the title (the show name for TV) and the activity
+    // to Watching, so Discord renders the natural "Watching {title}" line,
+    // regardless of media type or the activity_type overrides above.
+    pub watching_title_preset: bool,
+
+    // Suppresses presence until playback has progressed past this many
+    // seconds, avoiding flicker from briefly-previewed sessions.
+    pub min_progress_secs: u64,
+
+    // Skips trailers, behind-the-scenes, and other Plex "extra" sessions
+    // so they never trigger presence.
+    pub ignore_extras: bool,
+
+    // Log rotation: the log file is rotated once it exceeds this size, and
+    // at most this many rotated copies are kept. 0 for either disables that
+    // behavior (no cap / no backups).
+    pub log_max_size_mb: u64,
+    pub log_max_backups: u32,
+
+    // Shared by the console and file loggers. Overridden by the RUST_LOG
+    // env var for one-off debugging without touching the config file.
+    pub log_level: LevelFilter,
+
+    // Skips TLS certificate validation when talking to the Plex server, so
+    // local servers with a self-signed cert still connect. INSECURE: only
+    // enable this for servers you trust on your own network.
+    pub allow_insecure_tls: bool,
+
+    // Only ever try LAN/direct connections, skipping Plex's relay entirely.
+    // Good for users who are always on the same network as their server.
+    pub plex_local_only: bool,
+
+    // Rewrites a local connection's `https://` URI to plain `http://`,
+    // keeping its host (a `plex.direct` name stays as it is) and port,
+    // avoiding the untrusted-cert prompt TLS triggers on a LAN server. Off
+    // by default since it only applies to connections already
+    // classified as local; remote/relay connections always keep https.
+    pub prefer_http_for_local: bool,
+
+    // Some shared servers omit the `User` tag even on the owner's own
+    // session, which would otherwise filter it out entirely. When set, a
+    // username filter that matches nothing falls back to the sole active
+    // session rather than reporting nothing playing.
+    pub fallback_to_any_session_when_no_user: bool,
+
+    // The `X-Plex-Client-Identifier` sent with every Plex/plex.tv request.
+    // Generated once on first run and persisted here, so running two
+    // instances (e.g. two PCs) doesn't make Plex's device list treat them
+    // as the same device. Advanced users can set their own value instead.
+    pub plex_client_identifier: Option<String>,
+
+    // Overrides the HTTP `User-Agent` sent to Plex/plex.tv and TMDB/Jikan.
+    // None keeps the default `PresenceForPlex/<version>`.
+    pub custom_user_agent: Option<String>,
+
+    // Only monitor servers named here (matched by server name or, more
+    // robustly, `clientIdentifier`), instead of every owned/shared server
+    // the account can see. Empty means monitor all of them.
+    pub monitored_servers: Vec<String>,
+
+    // Falls back to polling /status/sessions at this interval when the SSE
+    // stream fails to open, e.g. behind a reverse proxy that breaks it. 0
+    // disables the fallback.
+    pub poll_fallback_interval_secs: u64,
+
+    // While an SSE stream stays open, periodically re-checks that the
+    // tracked session is still in /status/sessions, clearing presence if
+    // it's gone. Catches clients (e.g. browser players) that stop updating
+    // without ever sending a "stopped" notification. 0 disables the check.
+    pub stale_session_check_interval_secs: u64,
+
+    // Per-request timeout for Plex and plex.tv HTTP calls (sessions, server
+    // discovery, metadata lookups). Raise this on slow remote connections
+    // where 10s isn't enough and requests silently fail.
+    pub http_timeout_secs: u64,
+
+    // How long to wait for the SSE/websocket stream to open. An SSE stream
+    // that doesn't open in time falls back to polling (see
+    // `poll_fallback_interval_secs`); a websocket that doesn't open in time
+    // counts as a failed connection. Separate from `http_timeout_secs` since
+    // it's a one-time connect wait rather than a per-request timeout.
+    pub sse_connect_timeout_secs: u64,
+
+    // Which live-update mechanism to subscribe to for playback
+    // notifications. SSE works everywhere; some reverse proxies buffer or
+    // drop event streams entirely, so WebSocket is offered as a workaround
+    // when SSE is blocked.
+    pub notification_transport: NotificationTransport,
+
+    // Clears presence entirely while paused instead of showing the paused
+    // assets, for anyone who finds a long-paused presence confusing.
+    pub clear_on_pause: bool,
+
+    // A "HH:MM-HH:MM" window (local time) outside of which presence is
+    // suppressed, e.g. "08:00-23:00" to stay quiet overnight. Wraps past
+    // midnight if the start is after the end, e.g. "22:00-06:00". None
+    // (the default) means presence is always active. A value that doesn't
+    // parse is logged and treated as unset.
+    pub active_hours: Option<String>,
+
+    // Delays acting on a `Stopped` update by this many seconds, so a quick
+    // follow-up `Playing` (e.g. autoplay advancing to the next episode)
+    // cancels the clear instead of causing a brief flicker. 0 disables the
+    // debounce and clears immediately, as before.
+    pub stop_debounce_secs: u64,
+
+    // Serves artwork through the Plex server's own thumbnail transcoder
+    // instead of looking it up on TMDB/MusicBrainz. Useful for libraries
+    // with posters TMDB doesn't have, or when offline from those services.
+    pub use_plex_artwork: bool,
+
+    // Fronts `use_plex_artwork` URLs with a local proxy the app itself
+    // serves, so the Plex token never appears in the URL Discord fetches.
+    // Only useful alongside a tunnel/port-forward that makes the proxy
+    // reachable from wherever Discord renders the embed; purely local
+    // connections gain nothing since Discord can't reach localhost either way.
+    pub art_proxy_enabled: bool,
+    // Address the proxy listens on, e.g. "127.0.0.1:8765".
+    pub art_proxy_bind_addr: String,
+    // The externally reachable base URL (tunnel/port-forward) that maps to
+    // `art_proxy_bind_addr`, e.g. "https://my-tunnel.example.com". Required
+    // for `art_proxy_enabled` to take effect; with no base URL configured
+    // there's no address to hand Discord, so artwork falls back to the
+    // direct (token-bearing) transcode URL.
+    pub art_proxy_public_base_url: Option<String>,
+
+    // A local directory of fallback artwork images, served through the art
+    // proxy, used instead of the generic `plex_logo` large image when no
+    // art_url is available (e.g. every external provider is down). Files
+    // are named by media type: "movie.png", "episode.png", "track.png",
+    // "clip.png". Requires `art_proxy_public_base_url` too, since the
+    // image still needs a URL Discord can fetch. None disables this and
+    // keeps the plain `plex_logo` fallback.
+    pub offline_artwork_dir: Option<String>,
+    // Genre overrides for `offline_artwork_dir`, matched the same way as
+    // `genre_small_images`: the first of the item's genres with a matching
+    // key wins, and takes priority over the media-type file. Empty by
+    // default.
+    pub genre_offline_artwork: HashMap<String, String>,
+
+    // Pops a native toast when a new item starts playing. Windows only;
+    // ignored elsewhere.
+    pub show_notifications: bool,
+
+    // Logs the resolved Presence (details/state/image/buttons) on every
+    // update, so template changes can be checked without squinting at
+    // Discord. Presence is still sent as normal; this is purely additive.
+    pub preview_presence: bool,
+
+    // Shows a static activity instead of clearing presence entirely once
+    // playback stops.
+    pub idle_presence_enabled: bool,
+    pub idle_details: String,
+    pub idle_state: String,
+
+    // Maintains a rolling local watch log (title, type, start/stop times) as
+    // `history.json` in `Config::app_dir()`. Purely a side effect of running
+    // the app; nothing is sent anywhere.
+    pub history_enabled: bool,
+    pub history_max_entries: usize,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             discord_client_id: "1359742002618564618".to_string(),
+            anime_discord_client_id: None,
+            anime_genre_keywords: vec!["anime".to_string()],
             show_buttons: true,
-            show_progress: true,
+            button_sources: vec![ButtonSource::Mal, ButtonSource::Imdb, ButtonSource::Custom],
+            timestamp_mode: TimestampMode::Both,
+            freeze_paused_timestamp: true,
             show_artwork: true,
+            show_artwork_movies: None,
+            show_artwork_tv: None,
+            show_artwork_music: None,
             plex_token: None,
             enable_movies: true,
             enable_tv_shows: true,
             enable_music: true,
+            enable_clips: true,
             tmdb_token: None,
+            tmdb_image_size: "w500".to_string(),
+            tv_artwork_level: TvArtworkLevel::Season,
+            large_image_style: LargeImageStyle::Poster,
+            air_date_format: AirDateFormat::Iso,
+            genre_small_images: HashMap::new(),
             tv_details: "{show}".to_string(),
             tv_state: "S{season} · E{episode} - {title}".to_string(),
             tv_image_text: "{title}".to_string(),
+            anime_details: None,
+            anime_state: None,
+            anime_image_text: None,
             movie_details: "{title} ({year})".to_string(),
             movie_state: "{genres}".to_string(),
             movie_image_text: "{title}".to_string(),
+            clean_movie_titles: false,
+            episode_title_fallback: false,
+            template_max_len: None,
+            empty_field_placeholder: None,
             music_details: "{title}".to_string(),
             music_state: "{artist}".to_string(),
             music_image_text: "{album}".to_string(),
+            music_artist_forward: false,
+            clip_details: "{title}".to_string(),
+            clip_state: String::new(),
+            clip_image_text: "{title}".to_string(),
+            movie_activity_type: None,
+            tv_activity_type: None,
+            music_activity_type: None,
+            clip_activity_type: None,
+            group_activity_type: None,
+            watching_title_preset: false,
+            min_progress_secs: 0,
+            ignore_extras: false,
+            log_max_size_mb: 5,
+            log_max_backups: 3,
+            log_level: LevelFilter::Info,
+            allow_insecure_tls: false,
+            plex_local_only: false,
+            prefer_http_for_local: false,
+            fallback_to_any_session_when_no_user: false,
+            plex_client_identifier: None,
+            custom_user_agent: None,
+            monitored_servers: Vec::new(),
+            poll_fallback_interval_secs: 15,
+            stale_session_check_interval_secs: 60,
+            http_timeout_secs: 10,
+            sse_connect_timeout_secs: 15,
+            notification_transport: NotificationTransport::Sse,
+            clear_on_pause: false,
+            active_hours: None,
+            stop_debounce_secs: 3,
+            use_plex_artwork: false,
+            art_proxy_enabled: false,
+            art_proxy_bind_addr: "127.0.0.1:8765".to_string(),
+            art_proxy_public_base_url: None,
+            offline_artwork_dir: None,
+            genre_offline_artwork: HashMap::new(),
+            show_notifications: false,
+            preview_presence: false,
+            idle_presence_enabled: false,
+            idle_details: "Browsing Plex".to_string(),
+            idle_state: String::new(),
+            history_enabled: false,
+            history_max_entries: 200,
         }
     }
 }
 
 impl Config {
+    // Like `load`, but never logs and never touches the file on disk. Used
+    // to read settings needed before the logger itself is ready to run.
+    pub fn load_quiet() -> Self {
+        std::fs::read_to_string(Self::config_path())
+            .ok()
+            .and_then(|contents| serde_yml::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
     pub fn load() -> Self {
         let path = Self::config_path();
-        match std::fs::read_to_string(&path) {
-            Ok(contents) => match serde_yml::from_str(&contents) {
-                Ok(config) => config,
-                Err(e) => {
-                    log::error!("Failed to parse {}: {}", path.display(), e);
-                    let backup = path.with_extension("yaml.bak");
-                    match std::fs::rename(&path, &backup) {
-                        Ok(_) => log::warn!("Config backed up to {}", backup.display()),
-                        Err(e) => log::warn!("Config backup failed: {}", e),
+        let mut config = match std::fs::read_to_string(&path) {
+            Ok(contents) => match serde_yml::from_str::<serde_yml::Value>(&contents) {
+                Ok(raw) => {
+                    // Read the version straight off the raw document rather
+                    // than the struct we're about to parse into: a missing
+                    // key there becomes `CONFIG_VERSION` via
+                    // `#[serde(default)]`, which would make an old config
+                    // indistinguishable from a current one.
+                    let file_version =
+                        raw.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+                    match serde_yml::from_value::<Config>(raw) {
+                        Ok(mut config) => {
+                            if Self::migrate(&mut config, file_version) {
+                                log::info!(
+                                    "Migrated {} from config version {} to {}",
+                                    path.display(),
+                                    file_version,
+                                    CONFIG_VERSION
+                                );
+                                if let Err(e) = config.save() {
+                                    log::warn!("Failed to save migrated config: {}", e);
+                                }
+                            }
+                            config
+                        }
+                        Err(e) => Self::backup_and_reset(&path, e),
                     }
-                    Config::default()
                 }
+                Err(e) => Self::backup_and_reset(&path, e),
             },
             Err(e) => {
                 let config = Config::default();
@@ -78,7 +448,127 @@ impl Config {
                 }
                 config
             }
+        };
+        // Missing on a fresh config and on any config from before this field
+        // existed; generate it once here rather than via `#[serde(default)]`,
+        // which would hand out a new one on every load instead of persisting it.
+        if config.plex_client_identifier.is_none() {
+            config.plex_client_identifier = Some(generate_client_identifier());
+            if let Err(e) = config.save() {
+                log::warn!("Failed to save generated Plex client identifier: {}", e);
+            }
         }
+        config.validate();
+        config
+    }
+
+    // The identifier sent as `X-Plex-Client-Identifier`, falling back to the
+    // app-wide default if `load_quiet`/`Default` skipped generation.
+    pub fn client_identifier(&self) -> &str {
+        self.plex_client_identifier
+            .as_deref()
+            .unwrap_or(crate::plex_account::APP_NAME)
+    }
+
+    pub fn user_agent(&self) -> &str {
+        self.custom_user_agent
+            .as_deref()
+            .unwrap_or(DEFAULT_USER_AGENT)
+    }
+
+    // Upgrades an older on-disk config to the current layout, returning
+    // whether anything changed (so the caller knows to rewrite the file).
+    // `#[serde(default)]` already covers new fields showing up with sane
+    // defaults; this is for the changes serde can't handle unassisted, e.g.
+    // a rename or a field that splits into several. Add a match arm here
+    // per historical format break.
+    fn migrate(config: &mut Config, file_version: u32) -> bool {
+        if file_version >= CONFIG_VERSION {
+            return false;
+        }
+        // No format breaks yet: every field added since config versioning
+        // shipped has stayed additive, so there's nothing to do beyond
+        // stamping the current version. Future breaking changes land here,
+        // keyed off `file_version`.
+        config.version = CONFIG_VERSION;
+        true
+    }
+
+    // Warns about placeholders that don't match any known template
+    // variable, e.g. a typo'd `{titel}`, without rejecting the config.
+    fn validate(&self) {
+        if let Some(window) = &self.active_hours
+            && parse_active_hours(window).is_none()
+        {
+            log::warn!(
+                "Config field `active_hours` is not a valid \"HH:MM-HH:MM\" window: {}",
+                window
+            );
+        }
+        let templates = [
+            ("tv_details", &self.tv_details),
+            ("tv_state", &self.tv_state),
+            ("tv_image_text", &self.tv_image_text),
+            ("movie_details", &self.movie_details),
+            ("movie_state", &self.movie_state),
+            ("movie_image_text", &self.movie_image_text),
+            ("music_details", &self.music_details),
+            ("music_state", &self.music_state),
+            ("music_image_text", &self.music_image_text),
+            ("clip_details", &self.clip_details),
+            ("clip_state", &self.clip_state),
+            ("clip_image_text", &self.clip_image_text),
+        ];
+        for (field, template) in templates {
+            for placeholder in crate::presence::unknown_placeholders(template) {
+                log::warn!(
+                    "Config field `{}` references unknown placeholder `{{{}}}`: {}",
+                    field,
+                    placeholder,
+                    template
+                );
+            }
+        }
+        let anime_templates = [
+            ("anime_details", &self.anime_details),
+            ("anime_state", &self.anime_state),
+            ("anime_image_text", &self.anime_image_text),
+        ];
+        for (field, template) in anime_templates {
+            let Some(template) = template else { continue };
+            for placeholder in crate::presence::unknown_placeholders(template) {
+                log::warn!(
+                    "Config field `{}` references unknown placeholder `{{{}}}`: {}",
+                    field,
+                    placeholder,
+                    template
+                );
+            }
+        }
+    }
+
+    // Whether presence should currently be shown per `active_hours`. Always
+    // true if the window isn't set, and (since `validate` already warned
+    // about it) true if it doesn't parse rather than suppressing presence
+    // on a typo.
+    pub fn is_active_now(&self) -> bool {
+        let Some(window) = &self.active_hours else {
+            return true;
+        };
+        match parse_active_hours(window) {
+            Some((start, end)) => time_within_window(chrono::Local::now().time(), start, end),
+            None => true,
+        }
+    }
+
+    fn backup_and_reset(path: &Path, e: impl std::fmt::Display) -> Config {
+        log::error!("Failed to parse {}: {}", path.display(), e);
+        let backup = path.with_extension("yaml.bak");
+        match std::fs::rename(path, &backup) {
+            Ok(_) => log::warn!("Config backed up to {}", backup.display()),
+            Err(e) => log::warn!("Config backup failed: {}", e),
+        }
+        Config::default()
     }
 
     pub fn save(&self) -> std::io::Result<()> {
@@ -97,21 +587,87 @@ impl Config {
         Ok(())
     }
 
+    // Lets a portable install (e.g. run from a USB stick) or a sandboxed
+    // test keep its config file somewhere other than the OS config dir.
+    fn config_path_override() -> Option<PathBuf> {
+        std::env::var_os("PRESENCE_CONFIG_PATH").map(PathBuf::from)
+    }
+
     fn config_path() -> PathBuf {
-        Self::app_dir().join("config.yaml")
+        Self::config_path_override().unwrap_or_else(|| Self::app_dir().join("config.yaml"))
     }
 
     pub fn log_path() -> PathBuf {
         Self::app_dir().join("presence-for-plex.log")
     }
 
+    // Where the log file, overrides.yaml, and lock file live. Follows
+    // PRESENCE_CONFIG_PATH's parent directory when set, so everything lands
+    // alongside the config file instead of the OS config dir.
     pub fn app_dir() -> PathBuf {
+        if let Some(path) = Self::config_path_override() {
+            return path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| PathBuf::from("."));
+        }
         dirs::config_dir()
             .unwrap_or_else(|| PathBuf::from("."))
             .join("presence-for-plex")
     }
 }
 
+// Parses an `active_hours` string into its start/end times, e.g.
+// "08:00-23:00" -> (08:00, 23:00). None if it isn't two "HH:MM" times
+// joined by a dash.
+fn parse_active_hours(window: &str) -> Option<(chrono::NaiveTime, chrono::NaiveTime)> {
+    let (start, end) = window.split_once('-')?;
+    let start = chrono::NaiveTime::parse_from_str(start.trim(), "%H:%M").ok()?;
+    let end = chrono::NaiveTime::parse_from_str(end.trim(), "%H:%M").ok()?;
+    Some((start, end))
+}
+
+// Whether `now` falls within [start, end], wrapping past midnight if `start`
+// is after `end` (e.g. 22:00-06:00 covers 23:00 and 03:00 but not 12:00).
+fn time_within_window(
+    now: chrono::NaiveTime,
+    start: chrono::NaiveTime,
+    end: chrono::NaiveTime,
+) -> bool {
+    if start <= end {
+        now >= start && now <= end
+    } else {
+        now >= start || now <= end
+    }
+}
+
+// A random RFC 4122 v4 UUID, the same shape Plex's own clients use for
+// `X-Plex-Client-Identifier`.
+fn generate_client_identifier() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    format!(
+        "{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        bytes[0],
+        bytes[1],
+        bytes[2],
+        bytes[3],
+        bytes[4],
+        bytes[5],
+        bytes[6],
+        bytes[7],
+        bytes[8],
+        bytes[9],
+        bytes[10],
+        bytes[11],
+        bytes[12],
+        bytes[13],
+        bytes[14],
+        bytes[15],
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -145,4 +701,67 @@ mod tests {
     fn invalid_yaml_fails_to_parse() {
         assert!(serde_yml::from_str::<Config>("plex_token: [unclosed").is_err());
     }
+
+    #[test]
+    fn config_missing_the_version_key_parses_as_version_zero() {
+        let raw: serde_yml::Value = serde_yml::from_str("plex_token: abc123\n").unwrap();
+        let file_version = raw.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+        assert_eq!(file_version, 0);
+    }
+
+    #[test]
+    fn migrate_stamps_an_old_config_up_to_the_current_version() {
+        let mut config = Config::default();
+        assert!(Config::migrate(&mut config, 0));
+        assert_eq!(config.version, CONFIG_VERSION);
+    }
+
+    #[test]
+    fn migrate_is_a_no_op_for_an_up_to_date_config() {
+        let mut config = Config::default();
+        assert!(!Config::migrate(&mut config, CONFIG_VERSION));
+    }
+
+    #[test]
+    fn parse_active_hours_rejects_a_malformed_window() {
+        assert!(parse_active_hours("not a window").is_none());
+        assert!(parse_active_hours("08:00").is_none());
+        assert!(parse_active_hours("8am-11pm").is_none());
+    }
+
+    #[test]
+    fn time_within_window_handles_a_same_day_window() {
+        let start = chrono::NaiveTime::parse_from_str("08:00", "%H:%M").unwrap();
+        let end = chrono::NaiveTime::parse_from_str("23:00", "%H:%M").unwrap();
+        let noon = chrono::NaiveTime::parse_from_str("12:00", "%H:%M").unwrap();
+        let midnight = chrono::NaiveTime::parse_from_str("00:30", "%H:%M").unwrap();
+        assert!(time_within_window(noon, start, end));
+        assert!(!time_within_window(midnight, start, end));
+    }
+
+    #[test]
+    fn time_within_window_wraps_past_midnight_when_start_is_after_end() {
+        let start = chrono::NaiveTime::parse_from_str("22:00", "%H:%M").unwrap();
+        let end = chrono::NaiveTime::parse_from_str("06:00", "%H:%M").unwrap();
+        let late_night = chrono::NaiveTime::parse_from_str("23:30", "%H:%M").unwrap();
+        let early_morning = chrono::NaiveTime::parse_from_str("03:00", "%H:%M").unwrap();
+        let afternoon = chrono::NaiveTime::parse_from_str("15:00", "%H:%M").unwrap();
+        assert!(time_within_window(late_night, start, end));
+        assert!(time_within_window(early_morning, start, end));
+        assert!(!time_within_window(afternoon, start, end));
+    }
+
+    #[test]
+    fn is_active_now_is_always_true_without_a_configured_window() {
+        assert!(Config::default().is_active_now());
+    }
+
+    #[test]
+    fn is_active_now_is_true_for_a_malformed_window() {
+        let config = Config {
+            active_hours: Some("garbage".into()),
+            ..Config::default()
+        };
+        assert!(config.is_active_now());
+    }
 }
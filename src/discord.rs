@@ -1,30 +1,67 @@
 use discord_rich_presence::{DiscordIpc, DiscordIpcClient, activity};
-use log::{error, info};
-use std::time::{SystemTime, UNIX_EPOCH};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::media::PlaybackState;
+use crate::presence::format_mm_ss;
 
 // Timestamps this far out render as a frozen clock in Discord
 const PAUSED_OFFSET: i64 = 9999 * 3600;
+// Discord rejects details/state/large_text longer than this, in UTF-16 code units,
+// but we truncate by grapheme cluster which is close enough for practical text.
+const DISCORD_FIELD_MAX_LEN: usize = 128;
+// How long to wait for Discord's ACK of a SET_ACTIVITY frame before giving
+// up. The vendored IPC client's socket read has no deadline of its own, so
+// without this a frozen (but still connected) Discord client would hang
+// `confirm_activity_ack` forever.
+const ACK_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub struct DiscordClient {
-    client: DiscordIpcClient,
+    // Shared (rather than owned outright) so `confirm_activity_ack` can hand
+    // it to a blocking thread for the ACK read without fighting the
+    // borrow checker over a non-'static reference, and race that thread
+    // against `ACK_TIMEOUT` instead of awaiting it directly.
+    client: Arc<StdMutex<DiscordIpcClient>>,
+    client_id: String,
     connected: bool,
+    // Whether the last thing sent was an activity rather than a clear, for
+    // the heartbeat log to report "media active" without the caller having
+    // to track it separately.
+    has_activity: bool,
 }
 
 impl DiscordClient {
     pub fn new(client_id: &str) -> Self {
         Self {
-            client: DiscordIpcClient::new(client_id),
+            client: Arc::new(StdMutex::new(DiscordIpcClient::new(client_id))),
+            client_id: client_id.to_string(),
             connected: false,
+            has_activity: false,
         }
     }
 
+    // Reconnects under a different application id, e.g. to switch to an
+    // anime-specific Discord app with its own art keys. A no-op when
+    // `client_id` matches the one already in use, so callers can call this
+    // on every update without paying the reconnect cost each time.
+    pub fn ensure_client_id(&mut self, client_id: &str) {
+        if self.client_id == client_id {
+            return;
+        }
+        self.disconnect();
+        self.client_id = client_id.to_string();
+        self.client = Arc::new(StdMutex::new(DiscordIpcClient::new(client_id)));
+        self.connect();
+    }
+
     pub fn connect(&mut self) -> bool {
         if self.connected {
             self.disconnect();
         }
-        match self.client.connect() {
+        match self.client.lock().unwrap().connect() {
             Ok(_) => {
                 info!("Connected to Discord");
                 self.connected = true;
@@ -38,8 +75,9 @@ impl DiscordClient {
     }
 
     pub fn disconnect(&mut self) {
+        self.has_activity = false;
         if self.connected {
-            let _ = self.client.close();
+            let _ = self.client.lock().unwrap().close();
             self.connected = false;
         }
     }
@@ -48,7 +86,11 @@ impl DiscordClient {
         self.connected
     }
 
-    pub fn update(&mut self, p: &Presence) {
+    pub fn has_activity(&self) -> bool {
+        self.has_activity
+    }
+
+    pub async fn update(&mut self, p: &Presence) {
         if !self.connected {
             return;
         }
@@ -66,62 +108,179 @@ impl DiscordClient {
             .activity_type(p.activity_type.into())
             .status_display_type(display);
 
+        let details = truncate_for_discord(&p.details);
+        let state = truncate_for_discord(&p.state);
+
         // Discord rejects strings under 2 chars
-        if p.details.chars().count() >= 2 {
-            b = b.details(&p.details);
+        if details.chars().count() >= 2 {
+            b = b.details(details.as_ref());
         }
-        if p.state.chars().count() >= 2 {
-            b = b.state(&p.state);
+        if state.chars().count() >= 2 {
+            b = b.state(state.as_ref());
         }
 
-        if p.show_timestamps {
-            b = match p.playback_state {
+        let show_start = matches!(
+            p.timestamp_mode,
+            TimestampMode::Elapsed | TimestampMode::Both
+        );
+        let show_end = matches!(
+            p.timestamp_mode,
+            TimestampMode::Remaining | TimestampMode::Both
+        );
+        if show_start || show_end {
+            let (start, end) = match p.playback_state {
                 PlaybackState::Playing => {
                     let prog = (p.progress_ms / 1000) as i64;
                     let rem = (p.duration_ms.saturating_sub(p.progress_ms) / 1000) as i64;
-                    b.timestamps(activity::Timestamps::new().start(now - prog).end(now + rem))
+                    (now - prog, now + rem)
                 }
                 PlaybackState::Paused | PlaybackState::Buffering => {
                     let dur = (p.duration_ms / 1000) as i64;
-                    b.timestamps(
-                        activity::Timestamps::new()
-                            .start(now + PAUSED_OFFSET)
-                            .end(now + PAUSED_OFFSET + dur),
-                    )
+                    (now + PAUSED_OFFSET, now + PAUSED_OFFSET + dur)
                 }
             };
+            let mut timestamps = activity::Timestamps::new();
+            if show_start {
+                timestamps = timestamps.start(start);
+            }
+            if show_end {
+                timestamps = timestamps.end(end);
+            }
+            b = b.timestamps(timestamps);
         }
 
+        let large_image_text = truncate_for_discord(&p.large_image_text);
+        let paused_text = format_paused_text(p.progress_ms);
         let mut assets = activity::Assets::new();
         if let Some(ref url) = p.large_image {
-            assets = assets.large_image(url).large_text(&p.large_image_text);
+            assets = assets
+                .large_image(url)
+                .large_text(large_image_text.as_ref());
         }
-        if p.playback_state == PlaybackState::Paused {
-            assets = assets.small_image("paused").small_text("Paused");
+        match p.playback_state {
+            PlaybackState::Paused => {
+                assets = assets.small_image("paused").small_text(&paused_text);
+            }
+            PlaybackState::Buffering => {
+                assets = assets.small_image("buffering").small_text("Buffering…");
+            }
+            PlaybackState::Playing => {
+                if let Some(ref key) = p.small_image_key {
+                    assets = assets.small_image(key);
+                }
+            }
         }
         b = b.assets(assets);
 
+        if let (Some(size), Some(max)) = (p.party_size, p.party_max) {
+            b = b.party(activity::Party::new().size([size as i32, max as i32]));
+        }
+
         if !p.buttons.is_empty() {
             b = b.buttons(
                 p.buttons
                     .iter()
-                    .take(2)
+                    .take(crate::presence::MAX_BUTTONS)
                     .map(|btn| activity::Button::new(&btn.label, &btn.url))
                     .collect(),
             );
         }
 
-        if let Err(e) = self.client.set_activity(b) {
+        let sent = self.client.lock().unwrap().set_activity(b);
+        if let Err(e) = sent {
             error!("Presence update failed: {}", e);
             self.disconnect();
+            return;
         }
+
+        self.has_activity = true;
+        self.confirm_activity_ack().await;
     }
 
-    pub fn clear(&mut self) {
-        if self.connected {
-            let _ = self.client.clear_activity();
+    // Discord replies to every SET_ACTIVITY frame with an opcode-1 payload.
+    // An "ERROR" evt means the activity was rejected and never actually shown.
+    // The read itself is a blocking std socket call with no deadline (the
+    // vendored discord-rich-presence crate never sets one), so it runs on a
+    // blocking thread, raced against `ACK_TIMEOUT`, rather than being
+    // awaited directly — otherwise a frozen-but-connected Discord client
+    // would hang this forever while holding the caller's `discord` mutex,
+    // stalling every other presence update.
+    async fn confirm_activity_ack(&mut self) {
+        let client = Arc::clone(&self.client);
+        let read = tokio::task::spawn_blocking(
+            move || -> Result<Vec<u8>, discord_rich_presence::error::Error> {
+                let mut client = client.lock().unwrap();
+                let mut header = [0u8; 8];
+                client.read(&mut header)?;
+                let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+                let mut data = vec![0u8; len as usize];
+                client.read(&mut data)?;
+                Ok(data)
+            },
+        );
+
+        match tokio::time::timeout(ACK_TIMEOUT, read).await {
+            Ok(Ok(Ok(data))) => match serde_json::from_slice::<serde_json::Value>(&data) {
+                Ok(ack) if ack.get("evt").and_then(|e| e.as_str()) == Some("ERROR") => {
+                    let message = ack["data"]["message"].as_str().unwrap_or("unknown error");
+                    warn!("Discord rejected the presence update: {}", message);
+                }
+                Ok(_) => debug!("Discord ACKed the presence update"),
+                Err(e) => warn!("Could not parse Discord ACK: {}", e),
+            },
+            Ok(Ok(Err(e))) => warn!("Failed reading ACK from Discord: {}", e),
+            Ok(Err(e)) => warn!("ACK read task failed: {}", e),
+            Err(_) => {
+                warn!(
+                    "Timed out after {:?} waiting for Discord to ACK the presence update, discarding the stuck connection",
+                    ACK_TIMEOUT
+                );
+                // `spawn_blocking` tasks can't be cancelled, so the read above
+                // is still running and still holding `self.client`'s mutex --
+                // reusing it (or calling `disconnect()`, which would try to
+                // lock it too) would just deadlock on the same abandoned
+                // thread. Replace it with a fresh, unlocked client instead;
+                // `reconnect_discord` picks up the resulting disconnected
+                // state on its next tick.
+                self.client = Arc::new(StdMutex::new(DiscordIpcClient::new(&self.client_id)));
+                self.connected = false;
+                self.has_activity = false;
+            }
         }
     }
+
+    // Waits for Discord to ACK the clear so presence doesn't linger if the
+    // process exits (or disconnects) right after this call.
+    pub async fn clear(&mut self) {
+        if !self.connected {
+            return;
+        }
+        let cleared = self.client.lock().unwrap().clear_activity();
+        match cleared {
+            Ok(_) => self.confirm_activity_ack().await,
+            Err(e) => warn!("Failed to clear presence: {}", e),
+        }
+        self.has_activity = false;
+    }
+}
+
+fn format_paused_text(progress_ms: u64) -> String {
+    format!("Paused · {}", format_mm_ss(progress_ms))
+}
+
+// Truncates to Discord's field limit by grapheme cluster so multibyte
+// characters never get split, appending an ellipsis when anything is cut.
+fn truncate_for_discord(text: &str) -> std::borrow::Cow<'_, str> {
+    if text.graphemes(true).count() <= DISCORD_FIELD_MAX_LEN {
+        return std::borrow::Cow::Borrowed(text);
+    }
+    debug!("Truncating '{}' to fit Discord's field limit", text);
+    let mut truncated: String = text
+        .graphemes(true)
+        .take(DISCORD_FIELD_MAX_LEN - 1)
+        .collect();
+    truncated.push('…');
+    std::borrow::Cow::Owned(truncated)
 }
 
 #[derive(Debug, Clone)]
@@ -130,18 +289,54 @@ pub struct Presence {
     pub state: String,
     pub large_image: Option<String>,
     pub large_image_text: String,
+    // Small-image asset key from `Config::genre_small_images`, e.g. a
+    // "horror" badge. Only shown while playing, since Paused/Buffering
+    // already use the small-image slot for their own icon.
+    pub small_image_key: Option<String>,
     pub progress_ms: u64,
     pub duration_ms: u64,
-    pub show_timestamps: bool,
+    pub timestamp_mode: TimestampMode,
     pub activity_type: ActivityType,
     pub playback_state: PlaybackState,
     pub buttons: Vec<Button>,
+    pub party_size: Option<u32>,
+    pub party_max: Option<u32>,
 }
 
-#[derive(Debug, Clone, Copy)]
+impl Presence {
+    // A one-line, human-readable rendering for `preview_presence`, so users
+    // iterating on templates can see what would be sent without squinting at
+    // Discord's UI.
+    pub fn preview_line(&self) -> String {
+        let buttons = self
+            .buttons
+            .iter()
+            .map(|b| b.label.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "details={:?} state={:?} image={:?} buttons=[{}]",
+            self.details, self.state, self.large_image_text, buttons
+        )
+    }
+}
+
+// Which half of Discord's start/end timestamp pair to set, giving finer
+// control than a single on/off toggle over whether the activity shows an
+// elapsed clock, a remaining-time countdown, both, or neither.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimestampMode {
+    Elapsed,
+    Remaining,
+    Both,
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ActivityType {
     Watching,
     Listening,
+    Playing,
 }
 
 impl From<ActivityType> for activity::ActivityType {
@@ -149,12 +344,72 @@ impl From<ActivityType> for activity::ActivityType {
         match t {
             ActivityType::Watching => activity::ActivityType::Watching,
             ActivityType::Listening => activity::ActivityType::Listening,
+            ActivityType::Playing => activity::ActivityType::Playing,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Button {
     pub label: String,
     pub url: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paused_text_includes_elapsed_position() {
+        assert_eq!(format_paused_text(23 * 60_000 + 45_000), "Paused · 23:45");
+    }
+
+    #[test]
+    fn short_text_is_unchanged() {
+        assert_eq!(truncate_for_discord("hello"), "hello");
+    }
+
+    #[test]
+    fn long_text_is_truncated_with_ellipsis() {
+        let long = "a".repeat(200);
+        let truncated = truncate_for_discord(&long);
+        assert_eq!(truncated.graphemes(true).count(), DISCORD_FIELD_MAX_LEN);
+        assert!(truncated.ends_with('…'));
+    }
+
+    #[test]
+    fn truncation_is_grapheme_aware() {
+        // Each flag emoji is a multi-codepoint grapheme cluster; slicing by
+        // byte or char would split it and produce invalid output.
+        let long = "🇯🇵".repeat(200);
+        let truncated = truncate_for_discord(&long);
+        assert_eq!(truncated.graphemes(true).count(), DISCORD_FIELD_MAX_LEN);
+        assert!(truncated.ends_with('…'));
+    }
+
+    #[test]
+    fn preview_line_includes_every_field() {
+        let presence = Presence {
+            details: "Pilot".into(),
+            state: "The Show".into(),
+            large_image: Some("https://example.com/art.png".into()),
+            large_image_text: "The Show".into(),
+            small_image_key: None,
+            progress_ms: 0,
+            duration_ms: 0,
+            timestamp_mode: TimestampMode::None,
+            activity_type: ActivityType::Watching,
+            playback_state: PlaybackState::Playing,
+            buttons: vec![Button {
+                label: "View on IMDb".into(),
+                url: "https://www.imdb.com/title/tt1".into(),
+            }],
+            party_size: None,
+            party_max: None,
+        };
+        assert_eq!(
+            presence.preview_line(),
+            r#"details="Pilot" state="The Show" image="The Show" buttons=[View on IMDb]"#
+        );
+    }
+}
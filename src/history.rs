@@ -0,0 +1,148 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::media::MediaType;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub title: String,
+    pub media_type: MediaType,
+    pub started_at: u64,
+    // None while the item is still playing; filled in once it stops.
+    pub stopped_at: Option<u64>,
+}
+
+// A rolling local watch log, kept entirely on disk (no external service)
+// as a side effect of running the app. Capped at `max_entries` so it
+// doesn't grow forever.
+pub struct HistoryLog {
+    path: PathBuf,
+    max_entries: usize,
+    entries: Mutex<Vec<HistoryEntry>>,
+}
+
+impl HistoryLog {
+    pub fn new(max_entries: usize) -> Self {
+        Self::at(Config::app_dir().join("history.json"), max_entries)
+    }
+
+    fn at(path: PathBuf, max_entries: usize) -> Self {
+        let entries = Self::load(&path);
+        Self {
+            path,
+            max_entries,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    fn load(path: &PathBuf) -> Vec<HistoryEntry> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_default(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    // Appends a newly started item, dropping the oldest entries once the
+    // log exceeds `max_entries`.
+    pub fn start(&self, title: &str, media_type: MediaType) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.push(HistoryEntry {
+            title: title.to_string(),
+            media_type,
+            started_at: now(),
+            stopped_at: None,
+        });
+        if entries.len() > self.max_entries {
+            let excess = entries.len() - self.max_entries;
+            entries.drain(..excess);
+        }
+        self.save(&entries);
+    }
+
+    // Marks the most recent entry as stopped, if it's still open.
+    pub fn stop(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(last) = entries.last_mut()
+            && last.stopped_at.is_none()
+        {
+            last.stopped_at = Some(now());
+        }
+        self.save(&entries);
+    }
+
+    fn save(&self, entries: &[HistoryEntry]) {
+        let json = match serde_json::to_string_pretty(entries) {
+            Ok(json) => json,
+            Err(e) => {
+                warn!("Failed to serialize watch history: {}", e);
+                return;
+            }
+        };
+        if let Some(parent) = self.path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Err(e) = std::fs::write(&self.path, json) {
+            warn!("Failed to write {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "presence-for-plex-history-test-{name}-{:?}",
+            std::thread::current().id()
+        ))
+    }
+
+    #[test]
+    fn start_then_stop_records_both_timestamps() {
+        let path = temp_path("start-stop");
+        let log = HistoryLog::at(path.clone(), 100);
+        log.start("Pilot", MediaType::Episode);
+        log.stop();
+
+        let entries = HistoryLog::load(&path);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].title, "Pilot");
+        assert!(entries[0].stopped_at.is_some());
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn caps_the_log_at_max_entries() {
+        let path = temp_path("cap");
+        let log = HistoryLog::at(path.clone(), 2);
+        log.start("A", MediaType::Movie);
+        log.start("B", MediaType::Movie);
+        log.start("C", MediaType::Movie);
+
+        let entries = HistoryLog::load(&path);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].title, "B");
+        assert_eq!(entries[1].title, "C");
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn missing_file_starts_with_an_empty_log() {
+        let log = HistoryLog::at(PathBuf::from("/nonexistent/history.json"), 10);
+        assert!(log.entries.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,133 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+#[cfg(test)]
+use std::path::Path;
+use std::path::PathBuf;
+
+/// A `Write` implementor for `simplelog::WriteLogger` that rotates the log
+/// file once it grows past `max_bytes`, keeping at most `max_backups` older
+/// copies named `<path>.1`, `<path>.2`, ... (1 is the most recent).
+pub struct RotatingWriter {
+    path: PathBuf,
+    max_bytes: u64,
+    max_backups: u32,
+    file: File,
+    written: u64,
+}
+
+impl RotatingWriter {
+    pub fn new(path: PathBuf, max_bytes: u64, max_backups: u32) -> io::Result<Self> {
+        let file = File::create(&path)?;
+        Ok(Self {
+            path,
+            max_bytes,
+            max_backups,
+            file,
+            written: 0,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for n in (1..self.max_backups).rev() {
+            let from = self.backup_path(n);
+            if from.exists() {
+                fs::rename(&from, self.backup_path(n + 1))?;
+            }
+        }
+        if self.max_backups > 0 {
+            fs::rename(&self.path, self.backup_path(1))?;
+        }
+        self.file = File::create(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+
+    fn backup_path(&self, n: u32) -> PathBuf {
+        let mut name = self.path.as_os_str().to_os_string();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    }
+}
+
+impl Write for RotatingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.max_bytes > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "presence-for-plex-test-{}-{}.log",
+            name,
+            std::process::id()
+        ))
+    }
+
+    fn cleanup(path: &Path, max_backups: u32) {
+        let _ = fs::remove_file(path);
+        for n in 1..=max_backups {
+            let mut name = path.as_os_str().to_os_string();
+            name.push(format!(".{n}"));
+            let _ = fs::remove_file(PathBuf::from(name));
+        }
+    }
+
+    #[test]
+    fn rotates_once_size_cap_is_exceeded() {
+        let path = temp_path("rotate");
+        cleanup(&path, 2);
+        let mut writer = RotatingWriter::new(path.clone(), 10, 2).unwrap();
+
+        writer.write_all(b"12345").unwrap();
+        writer.write_all(b"67890").unwrap();
+        // Still under the cap, so nothing has rotated yet.
+        assert!(!backup_exists(&path, 1));
+
+        writer.write_all(b"abc").unwrap();
+        assert!(backup_exists(&path, 1));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "abc");
+
+        cleanup(&path, 2);
+    }
+
+    #[test]
+    fn keeps_only_max_backups_copies() {
+        let path = temp_path("cap-backups");
+        cleanup(&path, 2);
+        let mut writer = RotatingWriter::new(path.clone(), 1, 2).unwrap();
+
+        for chunk in [
+            b"a".as_slice(),
+            b"b".as_slice(),
+            b"c".as_slice(),
+            b"d".as_slice(),
+        ] {
+            writer.write_all(chunk).unwrap();
+        }
+
+        assert!(backup_exists(&path, 1));
+        assert!(backup_exists(&path, 2));
+        assert!(!backup_exists(&path, 3));
+
+        cleanup(&path, 2);
+    }
+
+    fn backup_exists(path: &Path, n: u32) -> bool {
+        let mut name = path.as_os_str().to_os_string();
+        name.push(format!(".{n}"));
+        PathBuf::from(name).exists()
+    }
+}
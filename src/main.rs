@@ -1,40 +1,100 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod art_proxy;
 mod config;
 mod discord;
+mod history;
+mod logging;
 mod media;
 mod metadata;
+mod overrides;
 mod plex_account;
 mod plex_server;
 mod presence;
+mod redact;
 #[cfg(feature = "tray")]
 mod tray;
 
 use config::Config;
 use discord::DiscordClient;
+use history::HistoryLog;
 use log::{error, info, warn};
-use media::{MediaType, MediaUpdate};
-use metadata::MetadataEnricher;
+use logging::RotatingWriter;
+use media::{MediaInfo, MediaType, MediaUpdate, PlaybackState};
+use metadata::{LargeImageStyle, MetadataEnricher, TvArtworkLevel};
 use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
-use plex_account::{APP_NAME, PlexAccount};
-use plex_server::PlexServer;
-use presence::build_presence;
-use simplelog::{CombinedLogger, Config as LogConfig, LevelFilter, SimpleLogger, WriteLogger};
+use plex_account::{AccountError, PlexAccount, ServerInfo};
+use plex_server::{NotificationTransport, PlexServer, PlexServerOptions};
+use presence::{build_idle_presence, build_presence};
+use simplelog::{CombinedLogger, Config as LogConfig, SimpleLogger, WriteLogger};
 use std::fs::File;
 use std::sync::Arc;
-use std::time::Duration;
+#[cfg(feature = "tray")]
+use std::sync::Mutex as StdMutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 use tokio::sync::{Mutex, mpsc};
 use tokio_util::sync::CancellationToken;
 #[cfg(feature = "tray")]
 use tray::{TrayCommand, TrayHandle, TrayStatus};
 
 const AUTH_TIMEOUT: Duration = Duration::from_secs(300);
-const AUTH_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const AUTH_POLL_INTERVAL_INITIAL: Duration = Duration::from_secs(2);
+const AUTH_POLL_INTERVAL_MAX: Duration = Duration::from_secs(10);
 const DISCOVERY_RETRY_INITIAL: Duration = Duration::from_secs(5);
 const DISCOVERY_RETRY_MAX: Duration = Duration::from_secs(300);
+const DISCORD_RECONNECT_INTERVAL: Duration = Duration::from_secs(30);
+// How often the heartbeat log line fires during long idle stretches.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(300);
+
+// Tracks what's currently shown so tray toggles know whether to clear
+// Discord, and remembers the last session so "Resume presence" doesn't have
+// to wait for a new SSE event to rebuild it.
+#[cfg(feature = "tray")]
+#[derive(Default)]
+struct PresenceState {
+    current_type: Option<MediaType>,
+    paused: bool,
+    last_info: Option<MediaInfo>,
+}
+
+// Bundles the config knobs that shape monitoring but don't change once a
+// monitoring session starts, so starting/restarting it doesn't blow out the
+// argument count every time a new knob is added.
+#[derive(Clone)]
+struct MonitoringOptions {
+    ignore_extras: bool,
+    allow_insecure_tls: bool,
+    local_only: bool,
+    prefer_http_for_local: bool,
+    fallback_to_any_session_when_no_user: bool,
+    poll_fallback_interval_secs: u64,
+    stale_session_check_interval_secs: u64,
+    use_plex_artwork: bool,
+    art_proxy_public_base_url: Option<String>,
+    art_proxy_allowed_origins: art_proxy::AllowedOrigins,
+    monitored_servers: Vec<String>,
+    http_timeout_secs: u64,
+    sse_connect_timeout_secs: u64,
+    notification_transport: NotificationTransport,
+    client_identifier: String,
+    user_agent: String,
+}
+
+// Same idea as `MonitoringOptions`, for the knobs that configure the
+// MetadataEnricher a monitoring session builds.
+#[derive(Clone)]
+struct EnricherOptions {
+    tmdb: Option<String>,
+    tmdb_image_size: String,
+    tv_artwork_level: TvArtworkLevel,
+    large_image_style: LargeImageStyle,
+    anime_genre_keywords: Vec<String>,
+    http_timeout_secs: u64,
+    user_agent: String,
+}
 
-#[tokio::main]
-async fn main() {
+fn main() {
     let _lock = match acquire_instance_lock() {
         Ok(f) => f,
         Err(e) => {
@@ -45,62 +105,281 @@ async fn main() {
 
     init_logging();
 
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build tokio runtime");
+
     if std::env::args().any(|a| a == "--auth") {
-        match run_auth().await {
-            Some(_) => info!("Token saved"),
-            None => error!("Auth failed or timed out"),
+        rt.block_on(async {
+            match run_auth(CancellationToken::new(), None).await {
+                Some(_) => info!("Token saved"),
+                None => error!("Auth failed or timed out"),
+            }
+        });
+        return;
+    }
+
+    let config = Config::load();
+
+    #[cfg(feature = "tray")]
+    let (tray_tx, tray_rx) = mpsc::unbounded_channel::<TrayCommand>();
+
+    // macOS requires the tray icon to be created, and its event loop kept
+    // running, on the process's real main thread for as long as the app is
+    // alive, so it's done here rather than inside `run`, before the rest of
+    // the app is handed off to a tokio runtime on a background thread.
+    #[cfg(all(target_os = "macos", feature = "tray"))]
+    let tray = tray::setup(
+        tray_tx,
+        config.plex_token.is_some(),
+        config.enable_movies,
+        config.enable_tv_shows,
+        config.enable_music,
+    );
+
+    #[cfg(all(target_os = "macos", feature = "tray"))]
+    {
+        let handle = std::thread::spawn(move || rt.block_on(run(config, tray_rx, tray)));
+        while !handle.is_finished() {
+            pump_macos();
+            std::thread::sleep(Duration::from_millis(16));
         }
+        handle.join().ok();
         return;
     }
 
-    let config = Arc::new(Config::load());
+    #[cfg(not(all(target_os = "macos", feature = "tray")))]
+    {
+        #[cfg(feature = "tray")]
+        rt.block_on(run(config, tray_tx, tray_rx));
+        #[cfg(not(feature = "tray"))]
+        rt.block_on(run(config));
+    }
+}
+
+async fn run(
+    config: Config,
+    #[cfg(all(feature = "tray", not(target_os = "macos")))] tray_tx: mpsc::UnboundedSender<
+        TrayCommand,
+    >,
+    #[cfg(feature = "tray")] tray_rx: mpsc::UnboundedReceiver<TrayCommand>,
+    #[cfg(all(target_os = "macos", feature = "tray"))] tray: Option<TrayHandle>,
+) {
+    let config = Arc::new(Mutex::new(config));
     let cancel = CancellationToken::new();
     let (media_tx, media_rx) = mpsc::unbounded_channel::<MediaUpdate>();
 
-    #[cfg(feature = "tray")]
-    let (tray_tx, tray_rx) = mpsc::unbounded_channel::<TrayCommand>();
     #[cfg(feature = "tray")]
     let (status_tx, status_rx) = mpsc::unbounded_channel::<TrayStatus>();
+    #[cfg(all(feature = "tray", not(target_os = "macos")))]
+    let tray = {
+        let c = config.lock().await;
+        tray::setup(
+            tray_tx,
+            c.plex_token.is_some(),
+            c.enable_movies,
+            c.enable_tv_shows,
+            c.enable_music,
+        )
+    };
     #[cfg(feature = "tray")]
-    let tray = tray::setup(tray_tx, config.plex_token.is_some());
+    let presence_state: Arc<StdMutex<PresenceState>> =
+        Arc::new(StdMutex::new(PresenceState::default()));
 
-    let mut discord = DiscordClient::new(&config.discord_client_id);
+    let (
+        discord_client_id,
+        plex_token,
+        tmdb_token,
+        tmdb_image_size,
+        tv_artwork_level,
+        large_image_style,
+        anime_genre_keywords,
+        ignore_extras,
+        allow_insecure_tls,
+        local_only,
+        prefer_http_for_local,
+        fallback_to_any_session_when_no_user,
+        poll_fallback_interval_secs,
+        stale_session_check_interval_secs,
+        use_plex_artwork,
+        art_proxy_enabled,
+        art_proxy_bind_addr,
+        art_proxy_public_base_url,
+        offline_artwork_dir,
+        monitored_servers,
+        http_timeout_secs,
+        sse_connect_timeout_secs,
+        notification_transport,
+        history_enabled,
+        history_max_entries,
+        client_identifier,
+        user_agent,
+    ) = {
+        let c = config.lock().await;
+        (
+            c.discord_client_id.clone(),
+            c.plex_token.clone(),
+            c.tmdb_token.clone(),
+            c.tmdb_image_size.clone(),
+            c.tv_artwork_level,
+            c.large_image_style,
+            c.anime_genre_keywords.clone(),
+            c.ignore_extras,
+            c.allow_insecure_tls,
+            c.plex_local_only,
+            c.prefer_http_for_local,
+            c.fallback_to_any_session_when_no_user,
+            c.poll_fallback_interval_secs,
+            c.stale_session_check_interval_secs,
+            c.use_plex_artwork,
+            c.art_proxy_enabled,
+            c.art_proxy_bind_addr.clone(),
+            c.art_proxy_public_base_url.clone(),
+            c.offline_artwork_dir.clone(),
+            c.monitored_servers.clone(),
+            c.http_timeout_secs,
+            c.sse_connect_timeout_secs,
+            c.notification_transport,
+            c.history_enabled,
+            c.history_max_entries,
+            c.client_identifier().to_string(),
+            c.user_agent().to_string(),
+        )
+    };
+    let history = history_enabled.then(|| Arc::new(HistoryLog::new(history_max_entries)));
+    let art_proxy_allowed_origins: art_proxy::AllowedOrigins =
+        Arc::new(std::sync::Mutex::new(std::collections::HashSet::new()));
+    if art_proxy_enabled && art_proxy_public_base_url.is_some() {
+        tokio::spawn(art_proxy::serve(
+            art_proxy_bind_addr,
+            reqwest::Client::new(),
+            offline_artwork_dir.map(std::path::PathBuf::from),
+            Arc::clone(&art_proxy_allowed_origins),
+        ));
+    }
+    let mut discord = DiscordClient::new(&discord_client_id);
     discord.connect();
     let discord = Arc::new(Mutex::new(discord));
+    tokio::spawn(reconnect_discord(
+        Arc::clone(&discord),
+        cancel.child_token(),
+    ));
+
+    let connected_servers = Arc::new(AtomicUsize::new(0));
+    tokio::spawn(heartbeat(
+        Arc::clone(&discord),
+        Arc::clone(&connected_servers),
+        cancel.child_token(),
+    ));
 
     #[cfg(feature = "tray")]
     let media_task = handle_media(
         media_rx,
         Arc::clone(&discord),
         Arc::clone(&config),
-        status_tx,
+        history.clone(),
+        status_tx.clone(),
+        Arc::clone(&presence_state),
     );
     #[cfg(not(feature = "tray"))]
-    let media_task = handle_media(media_rx, Arc::clone(&discord), Arc::clone(&config));
+    let media_task = handle_media(media_rx, Arc::clone(&discord), Arc::clone(&config), history);
     tokio::spawn(media_task);
 
-    let sse_cancel = config
-        .plex_token
-        .clone()
-        .map(|token| spawn_monitoring(token, config.tmdb_token.clone(), &cancel, &media_tx));
+    let monitoring_opts = MonitoringOptions {
+        ignore_extras,
+        allow_insecure_tls,
+        local_only,
+        prefer_http_for_local,
+        fallback_to_any_session_when_no_user,
+        poll_fallback_interval_secs,
+        stale_session_check_interval_secs,
+        use_plex_artwork,
+        art_proxy_public_base_url: art_proxy_public_base_url.filter(|_| art_proxy_enabled),
+        art_proxy_allowed_origins: Arc::clone(&art_proxy_allowed_origins),
+        monitored_servers,
+        http_timeout_secs,
+        sse_connect_timeout_secs,
+        notification_transport,
+        client_identifier,
+        user_agent: user_agent.clone(),
+    };
+    let enricher_opts = EnricherOptions {
+        tmdb: tmdb_token,
+        tmdb_image_size,
+        tv_artwork_level,
+        large_image_style,
+        anime_genre_keywords,
+        http_timeout_secs,
+        user_agent,
+    };
+    let sse_cancel = plex_token.map(|token| {
+        spawn_monitoring(
+            token,
+            enricher_opts,
+            monitoring_opts,
+            &cancel,
+            &media_tx,
+            #[cfg(feature = "tray")]
+            &status_tx,
+            Arc::clone(&connected_servers),
+        )
+    });
 
     #[cfg(feature = "tray")]
     run_tray(
-        tray, tray_rx, status_rx, sse_cancel, &config, &cancel, &media_tx,
+        tray,
+        tray_rx,
+        status_rx,
+        sse_cancel,
+        Arc::clone(&config),
+        Arc::clone(&discord),
+        presence_state,
+        &cancel,
+        &media_tx,
+        &status_tx,
+        connected_servers,
+        art_proxy_allowed_origins,
     )
     .await;
 
     #[cfg(not(feature = "tray"))]
     {
         let _ = sse_cancel;
-        tokio::signal::ctrl_c().await.ok();
+        shutdown_signal().await;
     }
 
     cancel.cancel();
-    discord.lock().await.disconnect();
+    {
+        let mut d = discord.lock().await;
+        d.clear().await;
+        d.disconnect();
+    }
     info!("Shutting down");
 }
 
+// Resolves on Ctrl+C, and on Unix also SIGTERM, so a `systemctl stop`/`docker
+// stop` takes the same shutdown path as quitting from the tray.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let Ok(mut sigterm) =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        else {
+            tokio::signal::ctrl_c().await.ok();
+            return;
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = sigterm.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await.ok();
+    }
+}
+
 fn acquire_instance_lock() -> Result<File, String> {
     let dir = Config::app_dir();
     std::fs::create_dir_all(&dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
@@ -115,14 +394,16 @@ fn acquire_instance_lock() -> Result<File, String> {
 fn init_logging() {
     let path = Config::log_path();
     std::fs::create_dir_all(path.parent().unwrap()).ok();
+    let rotation = Config::load_quiet();
     let level = std::env::var("RUST_LOG")
         .ok()
         .and_then(|s| s.parse().ok())
-        .unwrap_or(LevelFilter::Info);
+        .unwrap_or(rotation.log_level);
     let mut loggers: Vec<Box<dyn simplelog::SharedLogger>> =
         vec![SimpleLogger::new(level, LogConfig::default())];
-    if let Ok(file) = File::create(&path) {
-        loggers.push(WriteLogger::new(level, LogConfig::default(), file));
+    let max_bytes = rotation.log_max_size_mb * 1024 * 1024;
+    if let Ok(writer) = RotatingWriter::new(path.clone(), max_bytes, rotation.log_max_backups) {
+        loggers.push(WriteLogger::new(level, LogConfig::default(), writer));
     }
     let _ = CombinedLogger::init(loggers);
     info!("Starting Presence for Plex - Log: {}", path.display());
@@ -134,37 +415,49 @@ async fn run_tray(
     mut tray_rx: mpsc::UnboundedReceiver<TrayCommand>,
     mut status_rx: mpsc::UnboundedReceiver<TrayStatus>,
     mut sse_cancel: Option<CancellationToken>,
-    config: &Config,
+    config: Arc<Mutex<Config>>,
+    discord: Arc<Mutex<DiscordClient>>,
+    presence_state: Arc<StdMutex<PresenceState>>,
     cancel: &CancellationToken,
     media_tx: &mpsc::UnboundedSender<MediaUpdate>,
+    status_tx: &mpsc::UnboundedSender<TrayStatus>,
+    connected_servers: Arc<AtomicUsize>,
+    art_proxy_allowed_origins: art_proxy::AllowedOrigins,
 ) {
     let Some(tray) = tray else {
         warn!("Tray unavailable, Ctrl+C to quit");
-        tokio::signal::ctrl_c().await.ok();
+        shutdown_signal().await;
         return;
     };
 
-    // Only Windows/macOS need the UI loop pumped from this thread
-    let pump_period = if cfg!(any(windows, target_os = "macos")) {
+    // Only Windows needs the UI loop pumped from this thread; macOS's is
+    // pumped on the process's real main thread in `main`.
+    let pump_period = if cfg!(windows) {
         Duration::from_millis(16)
     } else {
         Duration::from_secs(3600)
     };
     let mut pump = tokio::time::interval(pump_period);
     let (auth_tx, mut auth_rx) = mpsc::channel::<Option<String>>(1);
+    let (auth_progress_tx, mut auth_progress_rx) = mpsc::unbounded_channel::<u32>();
     let mut auth_in_progress = false;
+    let mut auth_cancel: Option<CancellationToken> = None;
+    let mut shutdown = std::pin::pin!(shutdown_signal());
 
     loop {
         tokio::select! {
             biased;
+            _ = &mut shutdown => break,
             _ = pump.tick() => {
                 #[cfg(windows)]
                 pump_messages();
-                #[cfg(target_os = "macos")]
-                pump_macos();
+            }
+            Some(attempt) = auth_progress_rx.recv() => {
+                tray.set_auth_text(&format!("Cancel Authentication (attempt {})", attempt));
             }
             Some(token) = auth_rx.recv() => {
                 auth_in_progress = false;
+                auth_cancel = None;
                 match token {
                     Some(token) => {
                         tray.set_auth_text("Reauthenticate");
@@ -172,11 +465,60 @@ async fn run_tray(
                         if let Some(old) = sse_cancel.take() {
                             old.cancel();
                         }
-                        sse_cancel =
-                            Some(spawn_monitoring(token, config.tmdb_token.clone(), cancel, media_tx));
+                        let (enricher_opts, monitoring_opts) = {
+                            let c = config.lock().await;
+                            (
+                                EnricherOptions {
+                                    tmdb: c.tmdb_token.clone(),
+                                    tmdb_image_size: c.tmdb_image_size.clone(),
+                                    tv_artwork_level: c.tv_artwork_level,
+                                    large_image_style: c.large_image_style,
+                                    anime_genre_keywords: c.anime_genre_keywords.clone(),
+                                    http_timeout_secs: c.http_timeout_secs,
+                                    user_agent: c.user_agent().to_string(),
+                                },
+                                MonitoringOptions {
+                                    ignore_extras: c.ignore_extras,
+                                    allow_insecure_tls: c.allow_insecure_tls,
+                                    local_only: c.plex_local_only,
+                                    prefer_http_for_local: c.prefer_http_for_local,
+                                    fallback_to_any_session_when_no_user: c
+                                        .fallback_to_any_session_when_no_user,
+                                    poll_fallback_interval_secs: c.poll_fallback_interval_secs,
+                                    stale_session_check_interval_secs: c
+                                        .stale_session_check_interval_secs,
+                                    use_plex_artwork: c.use_plex_artwork,
+                                    art_proxy_public_base_url: c
+                                        .art_proxy_public_base_url
+                                        .clone()
+                                        .filter(|_| c.art_proxy_enabled),
+                                    art_proxy_allowed_origins: Arc::clone(&art_proxy_allowed_origins),
+                                    monitored_servers: c.monitored_servers.clone(),
+                                    http_timeout_secs: c.http_timeout_secs,
+                                    sse_connect_timeout_secs: c.sse_connect_timeout_secs,
+                                    notification_transport: c.notification_transport,
+                                    client_identifier: c.client_identifier().to_string(),
+                                    user_agent: c.user_agent().to_string(),
+                                },
+                            )
+                        };
+                        sse_cancel = Some(spawn_monitoring(
+                            token,
+                            enricher_opts,
+                            monitoring_opts,
+                            cancel,
+                            media_tx,
+                            status_tx,
+                            Arc::clone(&connected_servers),
+                        ));
                     }
                     None => {
-                        warn!("Auth failed or timed out");
+                        warn!("Auth failed, timed out, or was cancelled");
+                        tray.set_auth_text(if sse_cancel.is_some() {
+                            "Reauthenticate"
+                        } else {
+                            "Authenticate with Plex"
+                        });
                         if sse_cancel.is_none() {
                             tray.set_status_text(TrayStatus::NotAuthenticated.as_str());
                         }
@@ -188,48 +530,235 @@ async fn run_tray(
                 TrayCommand::Quit => break,
                 TrayCommand::Authenticate if !auth_in_progress => {
                     auth_in_progress = true;
+                    tray.set_status_text(TrayStatus::Authenticating.as_str());
+                    let token = CancellationToken::new();
+                    auth_cancel = Some(token.clone());
                     let auth_tx = auth_tx.clone();
+                    let auth_progress_tx = auth_progress_tx.clone();
                     tokio::spawn(async move {
-                        let _ = auth_tx.send(run_auth().await).await;
+                        let _ = auth_tx
+                            .send(run_auth(token, Some(auth_progress_tx)).await)
+                            .await;
                     });
                 }
+                // Clicking the same menu item again while a poll is already
+                // under way cancels it instead of starting a second one.
+                TrayCommand::Authenticate => {
+                    if let Some(token) = auth_cancel.take() {
+                        token.cancel();
+                    }
+                }
+                TrayCommand::ToggleMovies => {
+                    toggle_enabled(&tray, &config, &discord, &presence_state, MediaType::Movie).await;
+                }
+                TrayCommand::ToggleTvShows => {
+                    toggle_enabled(&tray, &config, &discord, &presence_state, MediaType::Episode).await;
+                }
+                TrayCommand::ToggleMusic => {
+                    toggle_enabled(&tray, &config, &discord, &presence_state, MediaType::Track).await;
+                }
+                TrayCommand::TogglePause => {
+                    toggle_pause(&tray, &config, &discord, &presence_state).await;
+                }
+                TrayCommand::OpenLog => {
+                    if let Err(e) = open::that(Config::log_path()) {
+                        warn!("Failed to open log file: {}", e);
+                    }
+                }
+                TrayCommand::OpenConfigFolder => {
+                    if let Err(e) = open::that(Config::app_dir()) {
+                        warn!("Failed to open config folder: {}", e);
+                    }
+                }
                 _ => {}
             }
         }
     }
 }
 
+// Flips the enable_* flag for `media_type`, persists the change, and clears
+// any presence of that type currently being shown.
+#[cfg(feature = "tray")]
+async fn toggle_enabled(
+    tray: &TrayHandle,
+    config: &Arc<Mutex<Config>>,
+    discord: &Arc<Mutex<DiscordClient>>,
+    presence_state: &Arc<StdMutex<PresenceState>>,
+    media_type: MediaType,
+) {
+    let enabled = {
+        let mut c = config.lock().await;
+        let flag = match media_type {
+            MediaType::Movie => &mut c.enable_movies,
+            MediaType::Episode => &mut c.enable_tv_shows,
+            MediaType::Track => &mut c.enable_music,
+            MediaType::Clip => &mut c.enable_clips,
+        };
+        *flag = !*flag;
+        if let Err(e) = c.save() {
+            error!("Config save failed: {}", e);
+        }
+        *flag
+    };
+    tray.set_checked(media_type, enabled);
+
+    if !enabled {
+        let was_showing = {
+            let mut s = presence_state.lock().unwrap();
+            let was = s.current_type == Some(media_type);
+            if was {
+                s.current_type = None;
+            }
+            was
+        };
+        if was_showing {
+            discord.lock().await.clear().await;
+        }
+    }
+}
+
+// Stops broadcasting to Discord without touching the enable_* flags, then
+// rebuilds presence from the last known session on resume.
+#[cfg(feature = "tray")]
+async fn toggle_pause(
+    tray: &TrayHandle,
+    config: &Arc<Mutex<Config>>,
+    discord: &Arc<Mutex<DiscordClient>>,
+    presence_state: &Arc<StdMutex<PresenceState>>,
+) {
+    let (paused, last_info) = {
+        let mut s = presence_state.lock().unwrap();
+        s.paused = !s.paused;
+        if s.paused {
+            s.current_type = None;
+        }
+        (s.paused, s.last_info.clone())
+    };
+    tray.set_pause_text(if paused {
+        "Resume presence"
+    } else {
+        "Pause presence"
+    });
+
+    if paused {
+        discord.lock().await.clear().await;
+    } else if let Some(info) = last_info
+        && show_presence(&info, config, discord).await
+    {
+        presence_state.lock().unwrap().current_type = Some(info.media_type);
+    }
+}
+
 fn spawn_monitoring(
     token: String,
-    tmdb: Option<String>,
+    enricher_opts: EnricherOptions,
+    opts: MonitoringOptions,
     cancel: &CancellationToken,
     media_tx: &mpsc::UnboundedSender<MediaUpdate>,
+    #[cfg(feature = "tray")] status_tx: &mpsc::UnboundedSender<TrayStatus>,
+    connected_servers: Arc<AtomicUsize>,
 ) -> CancellationToken {
     let c = cancel.child_token();
     let monitor_cancel = c.clone();
     let tx = media_tx.clone();
-    tokio::spawn(async move { begin_monitoring(token, tmdb, tx, monitor_cancel).await });
+    #[cfg(feature = "tray")]
+    let status_tx = status_tx.clone();
+    tokio::spawn(async move {
+        begin_monitoring(
+            token,
+            enricher_opts,
+            opts,
+            tx,
+            monitor_cancel,
+            #[cfg(feature = "tray")]
+            status_tx,
+            connected_servers,
+        )
+        .await
+    });
     c
 }
 
+// Empty `monitored_servers` keeps the pre-existing behavior of monitoring
+// everything the account can see. Otherwise a server matches if its name or
+// (more robustly, since renames don't break it) its `clientIdentifier`
+// appears in the list.
+fn is_monitored_server(server: &ServerInfo, monitored_servers: &[String]) -> bool {
+    monitored_servers.is_empty()
+        || monitored_servers
+            .iter()
+            .any(|s| *s == server.name || *s == server.client_identifier)
+}
+
 async fn begin_monitoring(
     token: String,
-    tmdb: Option<String>,
+    enricher_opts: EnricherOptions,
+    opts: MonitoringOptions,
     tx: mpsc::UnboundedSender<MediaUpdate>,
     cancel: CancellationToken,
+    #[cfg(feature = "tray")] status_tx: mpsc::UnboundedSender<TrayStatus>,
+    connected_servers: Arc<AtomicUsize>,
 ) {
-    let enricher = Arc::new(MetadataEnricher::new(tmdb));
-    let mut account = PlexAccount::new();
+    let enricher = Arc::new(MetadataEnricher::new(
+        enricher_opts.tmdb,
+        enricher_opts.tmdb_image_size,
+        enricher_opts.tv_artwork_level,
+        enricher_opts.large_image_style,
+        enricher_opts.anime_genre_keywords,
+        enricher_opts.http_timeout_secs,
+        &enricher_opts.user_agent,
+    ));
+
+    #[cfg(feature = "tray")]
+    if let Some(health) = enricher.check_tmdb_health().await {
+        let _ = status_tx.send(TrayStatus::from(health));
+    }
+    #[cfg(not(feature = "tray"))]
+    enricher.check_tmdb_health().await;
+
+    let mut account = PlexAccount::new(
+        opts.http_timeout_secs,
+        opts.client_identifier.clone(),
+        &opts.user_agent,
+    );
 
     // Retry discovery, the network may not be up yet at login
     let mut delay = DISCOVERY_RETRY_INITIAL;
     let servers = loop {
-        if account.username().is_none() && account.fetch_username(&token).await.is_none() {
-            warn!("Account fetch failed, retrying in {}s", delay.as_secs());
-        } else {
-            match account.get_servers(&token).await {
-                Some(s) if !s.is_empty() => break s,
-                _ => warn!("No servers found, retrying in {}s", delay.as_secs()),
+        if account.username().is_none() {
+            match account.fetch_username(&token).await {
+                Ok(_) => {}
+                Err(AccountError::Unauthorized) => {
+                    warn!("Plex token rejected as unauthorized, re-authentication required");
+                    #[cfg(feature = "tray")]
+                    let _ = status_tx.send(TrayStatus::ReauthRequired);
+                    return;
+                }
+                Err(AccountError::Other) => {
+                    warn!("Account fetch failed, retrying in {}s", delay.as_secs());
+                    tokio::select! {
+                        _ = cancel.cancelled() => return,
+                        _ = tokio::time::sleep(delay) => {}
+                    }
+                    delay = (delay * 2).min(DISCOVERY_RETRY_MAX);
+                    continue;
+                }
+            }
+        }
+        match account
+            .get_servers(&token, opts.prefer_http_for_local)
+            .await
+        {
+            Ok(s) if !s.is_empty() => break s,
+            Ok(_) => warn!("No servers found, retrying in {}s", delay.as_secs()),
+            Err(AccountError::Unauthorized) => {
+                warn!("Plex token rejected as unauthorized, re-authentication required");
+                #[cfg(feature = "tray")]
+                let _ = status_tx.send(TrayStatus::ReauthRequired);
+                return;
+            }
+            Err(AccountError::Other) => {
+                warn!("Server fetch failed, retrying in {}s", delay.as_secs())
             }
         }
         tokio::select! {
@@ -241,54 +770,472 @@ async fn begin_monitoring(
 
     let username = account.username().map(String::from);
     for srv in servers {
+        if !is_monitored_server(&srv, &opts.monitored_servers) {
+            info!("Server {} not in monitored_servers, skipping", srv.name);
+            continue;
+        }
         let Some(access) = srv.access_token else {
             continue;
         };
-        let server = PlexServer::new(srv.name, srv.connections, access, username.clone());
+        let connections = if opts.local_only {
+            srv.connections.into_iter().filter(|c| c.is_local).collect()
+        } else {
+            srv.connections
+        };
+        if connections.is_empty() {
+            warn!(
+                "Server {} has no local connections, skipping (local-only mode)",
+                srv.name
+            );
+            continue;
+        }
+        {
+            let mut allowed = opts.art_proxy_allowed_origins.lock().unwrap();
+            for c in &connections {
+                if let Ok(uri) = reqwest::Url::parse(&c.uri) {
+                    allowed.insert(uri.origin().ascii_serialization());
+                }
+            }
+        }
+        let server = PlexServer::new(
+            srv.name,
+            connections,
+            access,
+            username.clone(),
+            PlexServerOptions {
+                ignore_extras: opts.ignore_extras,
+                allow_insecure_tls: opts.allow_insecure_tls,
+                notification_transport: opts.notification_transport,
+                poll_fallback_interval_secs: opts.poll_fallback_interval_secs,
+                stale_session_check_interval_secs: opts.stale_session_check_interval_secs,
+                use_plex_artwork: opts.use_plex_artwork,
+                art_proxy_public_base_url: opts.art_proxy_public_base_url.clone(),
+                fallback_to_any_session_when_no_user: opts.fallback_to_any_session_when_no_user,
+                http_timeout_secs: opts.http_timeout_secs,
+                sse_connect_timeout_secs: opts.sse_connect_timeout_secs,
+                client_identifier: opts.client_identifier.clone(),
+                user_agent: opts.user_agent.clone(),
+            },
+        );
         let tx = tx.clone();
         let enricher = Arc::clone(&enricher);
         let c = cancel.clone();
+        #[cfg(feature = "tray")]
+        let status_tx = status_tx.clone();
+        let connected_servers = Arc::clone(&connected_servers);
         tokio::spawn(async move {
-            tokio::select! { _ = c.cancelled() => {} _ = server.start_monitoring(tx, enricher) => {} }
+            tokio::select! {
+                _ = c.cancelled() => {}
+                _ = server.start_monitoring(
+                    tx,
+                    enricher,
+                    #[cfg(feature = "tray")]
+                    status_tx,
+                    connected_servers,
+                ) => {}
+            }
         });
     }
 }
 
+// Retries the Discord connection in the background while it's down, so
+// presence is ready the moment media starts even if Discord wasn't open at
+// launch or dropped mid-run. Stops trying once connected.
+async fn reconnect_discord(discord: Arc<Mutex<DiscordClient>>, cancel: CancellationToken) {
+    let mut interval = tokio::time::interval(DISCORD_RECONNECT_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => return,
+            _ = interval.tick() => {
+                let mut d = discord.lock().await;
+                if !d.is_connected() {
+                    d.connect();
+                }
+            }
+        }
+    }
+}
+
+// Logs a periodic summary of Discord connection status, how many monitored
+// servers are currently reachable, and whether presence is active, so a
+// long quiet stretch in the log reads as "idle and healthy" instead of
+// looking like the app hung.
+async fn heartbeat(
+    discord: Arc<Mutex<DiscordClient>>,
+    connected_servers: Arc<AtomicUsize>,
+    cancel: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => return,
+            _ = interval.tick() => {
+                let d = discord.lock().await;
+                info!(
+                    "Heartbeat: Discord {}, {} server(s) connected, media {}",
+                    if d.is_connected() { "connected" } else { "disconnected" },
+                    connected_servers.load(Ordering::Relaxed),
+                    if d.has_activity() { "active" } else { "idle" },
+                );
+            }
+        }
+    }
+}
+
+// Applies `info` to Discord if its media type is enabled and past the
+// min-progress threshold. Returns whether presence was actually shown.
+async fn show_presence(
+    info: &MediaInfo,
+    config: &Mutex<Config>,
+    discord: &Mutex<DiscordClient>,
+) -> bool {
+    let c = config.lock().await;
+    let enabled = match info.media_type {
+        MediaType::Movie => c.enable_movies,
+        MediaType::Episode => c.enable_tv_shows,
+        MediaType::Track => c.enable_music,
+        MediaType::Clip => c.enable_clips,
+    };
+    let past_min_progress = info.view_offset_ms >= c.min_progress_secs * 1000;
+    if !enabled || !past_min_progress {
+        return false;
+    }
+    let client_id = if info.is_anime(&c.anime_genre_keywords) {
+        c.anime_discord_client_id
+            .as_deref()
+            .unwrap_or(&c.discord_client_id)
+    } else {
+        &c.discord_client_id
+    };
+    let presence = build_presence(info, &c);
+    if c.preview_presence {
+        info!("Preview: {}", presence.preview_line());
+    }
+    let mut d = discord.lock().await;
+    d.ensure_client_id(client_id);
+    if !d.is_connected() {
+        d.connect();
+    }
+    d.update(&presence).await;
+    true
+}
+
+// A buffering blip has to persist this long before it's allowed to flip the
+// displayed state; brief ones are ignored so the timestamp bar doesn't
+// freeze and unfreeze for every network hiccup.
+const BUFFERING_GRACE: Duration = Duration::from_secs(3);
+
+// How often `handle_media` re-checks `active_hours` against the clock while
+// something is playing, so a session that started inside the window still
+// gets cleared once it crosses the boundary rather than only on the next
+// incoming update.
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
 async fn handle_media(
     mut rx: mpsc::UnboundedReceiver<MediaUpdate>,
     discord: Arc<Mutex<DiscordClient>>,
-    config: Arc<Config>,
+    config: Arc<Mutex<Config>>,
+    history: Option<Arc<HistoryLog>>,
     #[cfg(feature = "tray")] status_tx: mpsc::UnboundedSender<TrayStatus>,
+    #[cfg(feature = "tray")] presence_state: Arc<StdMutex<PresenceState>>,
 ) {
-    while let Some(update) = rx.recv().await {
+    #[cfg(windows)]
+    let mut last_notified_key: Option<String> = None;
+    // The logical item (guid, falling back to rating_key) and server currently
+    // shown, so a second monitored server reporting the same item playing
+    // doesn't flip presence back and forth between the two.
+    let mut active_item: Option<(String, String)> = None;
+    // The show currently active (grandparent_key, falling back to show_name),
+    // so a `Stopped` immediately followed by a `Playing` for the next episode
+    // of the same show can be recognized as an auto-advance rather than a
+    // genuine stop, and carried over without a visible clear.
+    let mut active_series: Option<String> = None;
+    // The identity of the item the history log currently has an open entry
+    // for, so a continuing Playing update for the same item doesn't append
+    // a duplicate "start".
+    let mut history_item: Option<String> = None;
+    // The item currently within its buffering grace period and when that
+    // period started, so a run of quick buffering blips for the same item
+    // doesn't each reset the clock.
+    let mut pending_buffering: Option<(String, Instant)> = None;
+    // A `Stopped` update waiting out `stop_debounce_secs` before its clear
+    // actually happens, so a quick follow-up `Playing` (e.g. autoplay
+    // advancing to the next episode) can cancel it first. Carries the show
+    // that was active when the stop arrived, to recognize that follow-up as
+    // the same series continuing rather than just any new session.
+    let mut pending_stop: Option<(String, Instant, Duration, Option<String>)> = None;
+    // Whether presence is currently being withheld because `active_hours`
+    // says we're outside the configured window, so the clear only fires
+    // once per crossing instead of every tick.
+    let mut suppressed_by_schedule = false;
+    let mut schedule_tick = tokio::time::interval(SCHEDULE_CHECK_INTERVAL);
+
+    loop {
+        let update = match &pending_stop {
+            Some((_, since, debounce, _)) => {
+                let remaining = debounce.saturating_sub(since.elapsed());
+                tokio::select! {
+                    update = rx.recv() => update,
+                    _ = tokio::time::sleep(remaining) => {
+                        pending_stop = None;
+                        apply_stop(
+                            &discord,
+                            &config,
+                            &history,
+                            &mut active_item,
+                            &mut active_series,
+                            &mut history_item,
+                            #[cfg(windows)]
+                            &mut last_notified_key,
+                            #[cfg(feature = "tray")]
+                            &status_tx,
+                            #[cfg(feature = "tray")]
+                            &presence_state,
+                        )
+                        .await;
+                        continue;
+                    }
+                    _ = schedule_tick.tick() => {
+                        check_active_hours(&discord, &config, &active_item, &mut suppressed_by_schedule).await;
+                        continue;
+                    }
+                }
+            }
+            None => {
+                tokio::select! {
+                    update = rx.recv() => update,
+                    _ = schedule_tick.tick() => {
+                        check_active_hours(&discord, &config, &active_item, &mut suppressed_by_schedule).await;
+                        continue;
+                    }
+                }
+            }
+        };
+        let Some(update) = update else { break };
         match update {
             MediaUpdate::Playing(info) => {
+                let series = info
+                    .grandparent_key
+                    .clone()
+                    .or_else(|| info.show_name.clone());
+                // A quick follow-up `Playing` cancels any debounced clear.
+                // When it's for the next episode of the show that was just
+                // playing, that's autoplay advancing rather than a new,
+                // unrelated session, and presence carries over seamlessly.
+                // Otherwise the debounced clear never actually happened, so
+                // show it now rather than letting the old show's presence
+                // linger into an unrelated one's.
+                if let Some((_, _, _, stopped_series)) = pending_stop.take() {
+                    if stopped_series.is_some() && stopped_series == series {
+                        info!(
+                            "Auto-advance detected, continuing presence for {}",
+                            info.title
+                        );
+                    } else {
+                        discord.lock().await.clear().await;
+                    }
+                }
+
+                let identity = info.guid.clone().or_else(|| info.rating_key.clone());
+                if let (Some(id), Some((active_id, active_server))) = (&identity, &active_item)
+                    && id == active_id
+                    && active_server != &info.server
+                {
+                    info!(
+                        "Ignoring duplicate playback of {} reported by server {}, already shown from {}",
+                        info.title, info.server, active_server
+                    );
+                    continue;
+                }
+                if let Some(id) = identity {
+                    active_item = Some((id.clone(), info.server.clone()));
+                    active_series = series;
+                    if let Some(history) = &history
+                        && history_item.as_deref() != Some(id.as_str())
+                    {
+                        history_item = Some(id);
+                        history.start(&info.title, info.media_type);
+                    }
+                }
+
+                let current_id = active_item.as_ref().map(|(id, _)| id.as_str());
+                if info.state == PlaybackState::Buffering {
+                    let since = match &pending_buffering {
+                        Some((id, since)) if current_id == Some(id.as_str()) => *since,
+                        _ => Instant::now(),
+                    };
+                    pending_buffering = Some((current_id.unwrap_or_default().to_string(), since));
+                    if since.elapsed() < BUFFERING_GRACE {
+                        continue;
+                    }
+                } else {
+                    pending_buffering = None;
+                }
+
                 #[cfg(feature = "tray")]
                 let _ = status_tx.send(TrayStatus::from(info.state));
 
-                let enabled = match info.media_type {
-                    MediaType::Movie => config.enable_movies,
-                    MediaType::Episode => config.enable_tv_shows,
-                    MediaType::Track => config.enable_music,
+                #[cfg(feature = "tray")]
+                let paused = {
+                    let mut s = presence_state.lock().unwrap();
+                    s.last_info = Some((*info).clone());
+                    s.paused
                 };
-                if enabled {
-                    let mut d = discord.lock().await;
-                    if !d.is_connected() {
-                        d.connect();
+                #[cfg(not(feature = "tray"))]
+                let paused = false;
+
+                if paused {
+                    discord.lock().await.clear().await;
+                    continue;
+                }
+
+                if info.state == PlaybackState::Paused && config.lock().await.clear_on_pause {
+                    discord.lock().await.clear().await;
+                    continue;
+                }
+
+                if config.lock().await.is_active_now() {
+                    suppressed_by_schedule = false;
+                } else {
+                    if !suppressed_by_schedule {
+                        suppressed_by_schedule = true;
+                        discord.lock().await.clear().await;
                     }
-                    d.update(&build_presence(&info, &config));
+                    continue;
                 }
-            }
-            MediaUpdate::Stopped => {
-                #[cfg(feature = "tray")]
-                let _ = status_tx.send(TrayStatus::Idle);
 
-                discord.lock().await.clear();
+                #[cfg(windows)]
+                if info.state == PlaybackState::Playing
+                    && last_notified_key.as_deref() != info.rating_key.as_deref()
+                    && config.lock().await.show_notifications
+                {
+                    last_notified_key = info.rating_key.clone();
+                    notify_now_playing(&info);
+                }
+
+                if show_presence(&info, &config, &discord).await {
+                    #[cfg(feature = "tray")]
+                    {
+                        presence_state.lock().unwrap().current_type = Some(info.media_type);
+                    }
+                }
+            }
+            MediaUpdate::Stopped(server) => {
+                if active_item.as_ref().is_some_and(|(_, s)| *s != server) {
+                    // Some other server's session ended; the one we're
+                    // showing is unaffected.
+                    continue;
+                }
+                let debounce = Duration::from_secs(config.lock().await.stop_debounce_secs);
+                if debounce.is_zero() {
+                    apply_stop(
+                        &discord,
+                        &config,
+                        &history,
+                        &mut active_item,
+                        &mut active_series,
+                        &mut history_item,
+                        #[cfg(windows)]
+                        &mut last_notified_key,
+                        #[cfg(feature = "tray")]
+                        &status_tx,
+                        #[cfg(feature = "tray")]
+                        &presence_state,
+                    )
+                    .await;
+                } else {
+                    pending_stop = Some((server, Instant::now(), debounce, active_series.clone()));
+                }
             }
         }
     }
 }
 
+// Clears Discord (or shows the idle presence) and resets the tracking state
+// `handle_media` keeps between updates. Shared by the immediate path
+// (`stop_debounce_secs == 0`) and the debounce timeout in `handle_media`'s
+// main loop.
+#[allow(clippy::too_many_arguments)]
+async fn apply_stop(
+    discord: &Arc<Mutex<DiscordClient>>,
+    config: &Arc<Mutex<Config>>,
+    history: &Option<Arc<HistoryLog>>,
+    active_item: &mut Option<(String, String)>,
+    active_series: &mut Option<String>,
+    history_item: &mut Option<String>,
+    #[cfg(windows)] last_notified_key: &mut Option<String>,
+    #[cfg(feature = "tray")] status_tx: &mpsc::UnboundedSender<TrayStatus>,
+    #[cfg(feature = "tray")] presence_state: &Arc<StdMutex<PresenceState>>,
+) {
+    *active_item = None;
+    *active_series = None;
+    if let Some(history) = history {
+        history.stop();
+    }
+    *history_item = None;
+
+    #[cfg(windows)]
+    {
+        *last_notified_key = None;
+    }
+
+    #[cfg(feature = "tray")]
+    {
+        let _ = status_tx.send(TrayStatus::Idle);
+        let mut s = presence_state.lock().unwrap();
+        s.current_type = None;
+        s.last_info = None;
+    }
+
+    let c = config.lock().await;
+    if c.idle_presence_enabled {
+        discord.lock().await.update(&build_idle_presence(&c)).await;
+    } else {
+        discord.lock().await.clear().await;
+    }
+}
+
+// Clears presence if `active_hours` says we've crossed outside the window
+// since the last check, so a long-running session that started inside it
+// still gets suppressed at the boundary rather than only on its next update.
+// A no-op once `suppressed_by_schedule` is already set, and whenever nothing
+// is currently playing.
+async fn check_active_hours(
+    discord: &Arc<Mutex<DiscordClient>>,
+    config: &Arc<Mutex<Config>>,
+    active_item: &Option<(String, String)>,
+    suppressed_by_schedule: &mut bool,
+) {
+    if active_item.is_none() || *suppressed_by_schedule {
+        return;
+    }
+    if !config.lock().await.is_active_now() {
+        *suppressed_by_schedule = true;
+        discord.lock().await.clear().await;
+    }
+}
+
+// Pops a native toast for a newly-started item. Best-effort: a failure to
+// show it (e.g. no notification server registered) is just logged.
+#[cfg(windows)]
+fn notify_now_playing(info: &MediaInfo) {
+    use winrt_notification::{Duration as ToastDuration, Toast};
+
+    let subtitle = match info.media_type {
+        MediaType::Episode => info.show_name.clone(),
+        MediaType::Track => info.artist.clone(),
+        MediaType::Movie | MediaType::Clip => None,
+    };
+
+    let mut toast = Toast::new(Toast::POWERSHELL_APP_ID).title(&info.title);
+    if let Some(subtitle) = subtitle.as_deref() {
+        toast = toast.text1(subtitle);
+    }
+    if let Err(e) = toast.duration(ToastDuration::Short).show() {
+        warn!("Notification failed: {}", e);
+    }
+}
+
 #[cfg(windows)]
 fn pump_messages() {
     use windows_sys::Win32::UI::WindowsAndMessaging::{
@@ -309,13 +1256,27 @@ fn pump_macos() {
     CFRunLoop::run_in_mode(unsafe { kCFRunLoopDefaultMode }, 0.0, false);
 }
 
-async fn run_auth() -> Option<String> {
+// `cancel` lets a caller (the tray's "cancel authentication" action) abort an
+// in-progress poll early. `progress`, if given, is sent the attempt number
+// before each poll so a caller can surface "still waiting" to the user.
+async fn run_auth(
+    cancel: CancellationToken,
+    progress: Option<mpsc::UnboundedSender<u32>>,
+) -> Option<String> {
     info!("Starting Plex auth");
-    let account = PlexAccount::new();
+    // Loaded up front (rather than only at the end, to save the token) so
+    // the PIN request uses the same client identifier/user-agent/timeout
+    // that discovery uses afterward, instead of the library defaults.
+    let mut cfg = Config::load();
+    let account = PlexAccount::new(
+        cfg.http_timeout_secs,
+        cfg.client_identifier().to_string(),
+        cfg.user_agent(),
+    );
     let (pin_id, code) = account.request_pin().await?;
     let url = format!(
         "https://app.plex.tv/auth#?clientID={}&code={}&context%5Bdevice%5D%5Bproduct%5D=Presence%20for%20Plex",
-        utf8_percent_encode(APP_NAME, NON_ALPHANUMERIC),
+        utf8_percent_encode(cfg.client_identifier(), NON_ALPHANUMERIC),
         utf8_percent_encode(&code, NON_ALPHANUMERIC)
     );
     println!("Open to authenticate:\n{}", url);
@@ -323,18 +1284,32 @@ async fn run_auth() -> Option<String> {
         warn!("Browser failed: {}", e);
     }
 
+    // Paced from when each poll *started*, not fixed sleeps after it
+    // returns, so a slow `check_pin` (it can take close to its own 10s HTTP
+    // timeout on flaky networks) doesn't stretch the effective interval.
+    let mut interval = AUTH_POLL_INTERVAL_INITIAL;
+    let mut attempt: u32 = 0;
     let token = tokio::time::timeout(AUTH_TIMEOUT, async {
         loop {
-            tokio::time::sleep(AUTH_POLL_INTERVAL).await;
+            attempt += 1;
+            if let Some(tx) = &progress {
+                let _ = tx.send(attempt);
+            }
+            let poll_start = Instant::now();
             if let Some(t) = account.check_pin(pin_id).await {
-                return t;
+                return Some(t);
+            }
+            tokio::select! {
+                _ = cancel.cancelled() => return None,
+                _ = tokio::time::sleep(interval.saturating_sub(poll_start.elapsed())) => {}
             }
+            interval = (interval * 2).min(AUTH_POLL_INTERVAL_MAX);
         }
     })
     .await
-    .ok()?;
+    .ok()
+    .flatten()?;
 
-    let mut cfg = Config::load();
     cfg.plex_token = Some(token.clone());
     if let Err(e) = cfg.save() {
         error!("Config save failed: {}", e);
@@ -1,14 +1,22 @@
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug, Clone)]
 pub enum MediaUpdate {
     Playing(Box<MediaInfo>),
-    Stopped,
+    // Carries the name of the server whose session ended, so arbitration
+    // across multiple monitored servers can tell whether this is the one
+    // currently being shown.
+    Stopped(String),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum MediaType {
     Movie,
     Episode,
     Track,
+    // Plex's catch-all for shorts, e.g. music videos, that aren't tied to a
+    // movie or TV library the way a trailer/extra would be.
+    Clip,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -18,6 +26,19 @@ pub enum PlaybackState {
     Buffering,
 }
 
+impl PlaybackState {
+    // Ranks states when picking which of several sessions for the same user
+    // to surface, e.g. an actively Playing TV session over a Paused phone
+    // session that was simply forgotten.
+    pub(crate) fn priority(self) -> u8 {
+        match self {
+            PlaybackState::Playing => 2,
+            PlaybackState::Buffering => 1,
+            PlaybackState::Paused => 0,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MediaInfo {
     pub title: String,
@@ -27,8 +48,26 @@ pub struct MediaInfo {
     pub episode: Option<u32>,
     pub artist: Option<String>,
     pub album: Option<String>,
+    pub track_number: Option<u32>,
+    pub track_total: Option<u32>,
+    // The season's total episode count (its `leafCount`), fetched alongside
+    // the other follow-up metadata in `enrich_external_ids`. None for
+    // non-episodes and whenever Plex doesn't report it.
+    pub episode_total: Option<u32>,
     pub year: Option<u32>,
+    // Plex's `originallyAvailableAt`, as the raw "YYYY-MM-DD" string it
+    // reports. None for episodes/movies Plex has no air/release date for.
+    pub original_air_date: Option<String>,
+    pub rating: Option<String>,
     pub genres: Vec<String>,
+    pub directors: Vec<String>,
+    pub studio: Option<String>,
+    // The show's network/studio (e.g. "HBO"), fetched from the series'
+    // metadata since Plex doesn't set `studio` on the episode itself. None
+    // for non-episodes and whenever the show doesn't have one set.
+    pub network: Option<String>,
+    pub critic_rating: Option<f32>,
+    pub audience_rating: Option<f32>,
     pub duration_ms: u64,
     pub view_offset_ms: u64,
     pub state: PlaybackState,
@@ -37,9 +76,84 @@ pub struct MediaInfo {
     pub mal_id: Option<String>,
     pub art_url: Option<String>,
     pub rating_key: Option<String>,
+    // Plex's universal content id (e.g. "plex://movie/5d7..."), stable across
+    // servers for matched library items unlike `rating_key`. Used to tell
+    // whether two servers are reporting the same logical item.
+    pub guid: Option<String>,
+    // Which monitored server this update came from, for the same
+    // cross-server arbitration `guid` enables.
+    pub server: String,
     // Plex library keys for follow-up metadata requests
     pub(crate) grandparent_key: Option<String>,
+    pub(crate) parent_key: Option<String>,
     pub(crate) key: Option<String>,
+    // Intro/credits chapter markers, used to smooth over the offset jump
+    // when the user skips an intro rather than treating it as a plain seek
+    pub(crate) markers: Vec<Marker>,
+    // How many sessions are watching this same item together, e.g. Plex's
+    // Watch Together. None for an ordinary solo session.
+    pub party_size: Option<u32>,
+    pub party_max: Option<u32>,
+    // The currently selected audio/subtitle track's language, if Plex reports
+    // one as selected. None when no track is selected (e.g. subtitles off).
+    pub audio_lang: Option<String>,
+    pub sub_lang: Option<String>,
+    // "Direct Play" or "Transcode", resolved from the active session. None if
+    // the session couldn't be found in /status/sessions.
+    pub playback_method: Option<String>,
+    // The playing device's product name (e.g. "Plex for Apple TV"), resolved
+    // from the active session's Player tag for the `{device}` placeholder.
+    // None if the session couldn't be found in /status/sessions.
+    pub device: Option<String>,
+    // Buttons from a manual override entry for this rating_key, appended to
+    // whatever build_presence already derives (e.g. MAL/IMDb links).
+    pub extra_buttons: Vec<crate::discord::Button>,
+    // A Live TV & DVR session: Plex reports these with no meaningful
+    // `duration_ms` and odd/absent episode metadata, so they get their own
+    // detection rather than rendering a broken "ends in 9999 hours" progress
+    // bar like a normal episode would.
+    pub is_live: bool,
+    // The live channel's name (Plex reports it as the session's
+    // grandparent title), for the `{channel}` placeholder. None unless
+    // `is_live` is set.
+    pub channel: Option<String>,
+    // A Plex Watch Together session: other sessions are watching this same
+    // item alongside this one. Distinct from `party_size`, which just counts
+    // participants; this is about the text (`{is_group}`/`{group_name}`).
+    pub is_group: bool,
+    // The other participants' usernames, comma-joined. None unless
+    // `is_group` is set, or if Plex didn't report a username for any of them.
+    pub group_name: Option<String>,
+    // The next episode's title in the season, fetched alongside the other
+    // follow-up metadata in `enrich_external_ids`. None for non-episodes,
+    // the season finale, and whenever Plex doesn't report one.
+    pub next_title: Option<String>,
+    // Classical music: the composer, from a `Composer` tag if the library
+    // has one, else the album's studio field (a common tagging workaround
+    // in libraries without composer support). None for non-classical tracks.
+    pub composer: Option<String>,
+    // Classical music: the work's title (e.g. "Symphony No. 5"), from
+    // `originalTitle`, when `title` itself is just the movement name.
+    pub work: Option<String>,
+}
+
+impl MediaInfo {
+    // Matches each genre against `keywords` as a case-insensitive substring,
+    // so a custom genre/collection like "Anime (Subbed)" still counts.
+    pub fn is_anime(&self, keywords: &[String]) -> bool {
+        self.genres.iter().any(|g| {
+            keywords
+                .iter()
+                .any(|k| g.to_ascii_lowercase().contains(&k.to_ascii_lowercase()))
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Marker {
+    pub(crate) marker_type: String,
+    pub(crate) start_ms: u64,
+    pub(crate) end_ms: u64,
 }
 
 #[cfg(test)]
@@ -53,8 +167,18 @@ impl MediaInfo {
             episode: None,
             artist: None,
             album: None,
+            track_number: None,
+            track_total: None,
+            episode_total: None,
             year: None,
+            original_air_date: None,
+            rating: None,
             genres: Vec::new(),
+            directors: Vec::new(),
+            studio: None,
+            network: None,
+            critic_rating: None,
+            audience_rating: None,
             duration_ms: 0,
             view_offset_ms: 0,
             state: PlaybackState::Playing,
@@ -63,8 +187,53 @@ impl MediaInfo {
             mal_id: None,
             art_url: None,
             rating_key: None,
+            guid: None,
+            server: "Test Server".into(),
             grandparent_key: None,
+            parent_key: None,
             key: None,
+            markers: Vec::new(),
+            party_size: None,
+            party_max: None,
+            audio_lang: None,
+            sub_lang: None,
+            playback_method: None,
+            device: None,
+            extra_buttons: Vec::new(),
+            is_live: false,
+            channel: None,
+            is_group: false,
+            group_name: None,
+            next_title: None,
+            composer: None,
+            work: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_anime_matches_the_genre_case_insensitively() {
+        let mut info = MediaInfo::test_stub(MediaType::Episode);
+        info.genres = vec!["Comedy".into(), "ANIME".into()];
+        assert!(info.is_anime(&["anime".to_string()]));
+    }
+
+    #[test]
+    fn is_anime_is_false_without_a_matching_keyword() {
+        let mut info = MediaInfo::test_stub(MediaType::Movie);
+        info.genres = vec!["Animation".into()];
+        assert!(!info.is_anime(&["anime".to_string()]));
+    }
+
+    #[test]
+    fn is_anime_matches_a_custom_keyword_as_a_substring() {
+        let mut info = MediaInfo::test_stub(MediaType::Episode);
+        info.genres = vec!["Donghua".into()];
+        assert!(!info.is_anime(&["anime".to_string()]));
+        assert!(info.is_anime(&["donghua".to_string()]));
+    }
+}
@@ -0,0 +1,145 @@
+use log::warn;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+use crate::config::Config;
+use crate::discord::Button;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MetadataOverride {
+    pub art_url: Option<String>,
+    pub mal_id: Option<String>,
+    #[serde(default)]
+    pub buttons: Vec<Button>,
+}
+
+#[derive(Default, Deserialize)]
+struct OverridesFile {
+    #[serde(default)]
+    overrides: HashMap<String, MetadataOverride>,
+}
+
+struct Loaded {
+    overrides: HashMap<String, MetadataOverride>,
+    mtime: Option<SystemTime>,
+}
+
+// Lets users hand-fix a handful of stubborn items (e.g. an obscure anime
+// TMDB/MAL can't find, or a custom button) by rating_key without forking the
+// whole enrichment pipeline. Reloaded lazily by checking the file's mtime on
+// each lookup, so edits take effect without restarting the app.
+pub struct Overrides {
+    path: PathBuf,
+    loaded: RwLock<Loaded>,
+}
+
+impl Overrides {
+    pub fn new() -> Self {
+        Self::at(Config::app_dir().join("overrides.yaml"))
+    }
+
+    fn at(path: PathBuf) -> Self {
+        let loaded = Self::load(&path);
+        Self {
+            path,
+            loaded: RwLock::new(loaded),
+        }
+    }
+
+    pub fn get(&self, rating_key: &str) -> Option<MetadataOverride> {
+        self.reload_if_changed();
+        self.loaded
+            .read()
+            .unwrap()
+            .overrides
+            .get(rating_key)
+            .cloned()
+    }
+
+    fn reload_if_changed(&self) {
+        let current_mtime = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .ok();
+        if current_mtime == self.loaded.read().unwrap().mtime {
+            return;
+        }
+        *self.loaded.write().unwrap() = Self::load(&self.path);
+    }
+
+    fn load(path: &PathBuf) -> Loaded {
+        let mtime = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let overrides = match std::fs::read_to_string(path) {
+            Ok(contents) => match serde_yml::from_str::<OverridesFile>(&contents) {
+                Ok(file) => file.overrides,
+                Err(e) => {
+                    warn!("Failed to parse {}: {}", path.display(), e);
+                    HashMap::new()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                warn!("Could not read {}: {}", path.display(), e);
+                HashMap::new()
+            }
+        };
+        Loaded { overrides, mtime }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_file_yields_no_overrides() {
+        let overrides = Overrides::at(PathBuf::from("/nonexistent/overrides.yaml"));
+        assert!(overrides.get("12345").is_none());
+    }
+
+    #[test]
+    fn parses_overrides_keyed_by_rating_key() {
+        let dir = std::env::temp_dir().join(format!(
+            "presence-for-plex-overrides-test-{:?}",
+            std::thread::current().id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("overrides.yaml");
+        std::fs::write(
+            &path,
+            "overrides:\n  \"12345\":\n    art_url: https://example.com/art.png\n    mal_id: \"42\"\n    buttons:\n      - label: MyAnimeList\n        url: https://myanimelist.net/anime/42\n",
+        )
+        .unwrap();
+
+        let overrides = Overrides::at(path.clone());
+        let entry = overrides.get("12345").expect("override present");
+        assert_eq!(
+            entry.art_url.as_deref(),
+            Some("https://example.com/art.png")
+        );
+        assert_eq!(entry.mal_id.as_deref(), Some("42"));
+        assert_eq!(entry.buttons.len(), 1);
+        assert_eq!(entry.buttons[0].label, "MyAnimeList");
+        assert!(overrides.get("99999").is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn invalid_yaml_is_treated_as_no_overrides() {
+        let dir = std::env::temp_dir().join(format!(
+            "presence-for-plex-overrides-test-invalid-{:?}",
+            std::thread::current().id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("overrides.yaml");
+        std::fs::write(&path, "overrides: [unclosed").unwrap();
+
+        let overrides = Overrides::at(path.clone());
+        assert!(overrides.get("12345").is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
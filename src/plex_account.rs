@@ -1,20 +1,32 @@
 use log::info;
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
 use serde::Deserialize;
 use std::time::Duration;
 
 pub const APP_NAME: &str = "presence-for-plex";
 const PLEX_API: &str = "https://plex.tv/api/v2";
-const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountError {
+    // Plex rejected the token itself (401), as opposed to a transient
+    // network/server hiccup. The caller should stop retrying and prompt
+    // re-auth rather than backing off and trying the same token again.
+    Unauthorized,
+    Other,
+}
 
 pub struct PlexAccount {
     client: Client,
     username: Option<String>,
+    client_identifier: String,
 }
 
 #[derive(Debug)]
 pub struct ServerInfo {
     pub name: String,
+    // Stable across renames, unlike `name`, so it's the more robust thing to
+    // match a `monitored_servers` entry against.
+    pub client_identifier: String,
     pub access_token: Option<String>,
     pub connections: Vec<ServerConnection>,
 }
@@ -22,17 +34,20 @@ pub struct ServerInfo {
 #[derive(Debug, Clone)]
 pub struct ServerConnection {
     pub uri: String,
+    pub is_local: bool,
+    pub is_relay: bool,
 }
 
 impl PlexAccount {
-    pub fn new() -> Self {
+    pub fn new(http_timeout_secs: u64, client_identifier: String, user_agent: &str) -> Self {
         Self {
             client: Client::builder()
-                .user_agent("PresenceForPlex/1.0")
-                .timeout(TIMEOUT)
+                .user_agent(user_agent.to_string())
+                .timeout(Duration::from_secs(http_timeout_secs))
                 .build()
                 .expect("HTTP client"),
             username: None,
+            client_identifier,
         }
     }
 
@@ -40,23 +55,27 @@ impl PlexAccount {
         self.username.as_deref()
     }
 
-    pub async fn fetch_username(&mut self, token: &str) -> Option<String> {
-        let json: serde_json::Value = self
+    pub async fn fetch_username(&mut self, token: &str) -> Result<String, AccountError> {
+        let resp = self
             .client
             .get(format!("{}/user", PLEX_API))
             .header("Accept", "application/json")
             .header("X-Plex-Token", token)
-            .header("X-Plex-Client-Identifier", APP_NAME)
+            .header("X-Plex-Client-Identifier", &self.client_identifier)
             .send()
             .await
-            .ok()?
-            .json()
-            .await
-            .ok()?;
-        let username = json["username"].as_str()?.to_string();
+            .map_err(|_| AccountError::Other)?;
+        if resp.status() == StatusCode::UNAUTHORIZED {
+            return Err(AccountError::Unauthorized);
+        }
+        let json: serde_json::Value = resp.json().await.map_err(|_| AccountError::Other)?;
+        let username = json["username"]
+            .as_str()
+            .ok_or(AccountError::Other)?
+            .to_string();
         info!("Logged in as: {}", username);
         self.username = Some(username.clone());
-        Some(username)
+        Ok(username)
     }
 
     pub async fn request_pin(&self) -> Option<(u64, String)> {
@@ -65,7 +84,7 @@ impl PlexAccount {
             .post(format!("{}/pins", PLEX_API))
             .header("Accept", "application/json")
             .header("X-Plex-Product", "Presence for Plex")
-            .header("X-Plex-Client-Identifier", APP_NAME)
+            .header("X-Plex-Client-Identifier", &self.client_identifier)
             .query(&[("strong", "true")])
             .send()
             .await
@@ -81,7 +100,7 @@ impl PlexAccount {
             .client
             .get(format!("{}/pins/{}", PLEX_API, pin_id))
             .header("Accept", "application/json")
-            .header("X-Plex-Client-Identifier", APP_NAME)
+            .header("X-Plex-Client-Identifier", &self.client_identifier)
             .send()
             .await
             .ok()?
@@ -94,39 +113,90 @@ impl PlexAccount {
             .map(String::from)
     }
 
-    pub async fn get_servers(&self, token: &str) -> Option<Vec<ServerInfo>> {
-        let resources: Vec<PlexResource> = self
+    pub async fn get_servers(
+        &self,
+        token: &str,
+        prefer_http_for_local: bool,
+    ) -> Result<Vec<ServerInfo>, AccountError> {
+        let resp = self
             .client
             .get(format!("{}/resources", PLEX_API))
             .header("Accept", "application/json")
             .header("X-Plex-Token", token)
-            .header("X-Plex-Client-Identifier", APP_NAME)
+            .header("X-Plex-Client-Identifier", &self.client_identifier)
             .query(&[("includeHttps", "1"), ("includeRelay", "1")])
             .send()
             .await
-            .ok()?
-            .json()
-            .await
-            .ok()?;
+            .map_err(|_| AccountError::Other)?;
+        if resp.status() == StatusCode::UNAUTHORIZED {
+            return Err(AccountError::Unauthorized);
+        }
+        let resources: Vec<PlexResource> = resp.json().await.map_err(|_| AccountError::Other)?;
+
+        Ok(resources
+            .into_iter()
+            .filter(|r| r.provides.contains("server") && !r.connections.is_empty())
+            .map(|r| {
+                info!("Server: {} ({} connections)", r.name, r.connections.len());
+                let mut connections: Vec<ServerConnection> = r
+                    .connections
+                    .into_iter()
+                    .map(|c| {
+                        let is_local = is_local_uri(&c.uri);
+                        let uri = if prefer_http_for_local && is_local {
+                            prefer_http(c.uri)
+                        } else {
+                            c.uri
+                        };
+                        ServerConnection {
+                            is_local,
+                            is_relay: c.relay,
+                            uri,
+                        }
+                    })
+                    .collect();
+                // Try local/direct connections first so LAN users skip the
+                // relay's extra latency and untrusted-cert prompts.
+                connections.sort_by_key(|c| (c.is_relay, !c.is_local));
+                ServerInfo {
+                    name: r.name,
+                    client_identifier: r.client_identifier,
+                    access_token: r.access_token,
+                    connections,
+                }
+            })
+            .collect())
+    }
+}
+
+// Plex's `plex.direct` hostnames encode the server's LAN address as their
+// first dash-separated label, e.g. `192-168-1-50.<uuid>.plex.direct`.
+fn is_local_uri(uri: &str) -> bool {
+    let host = uri
+        .split("://")
+        .nth(1)
+        .unwrap_or(uri)
+        .split(['/', ':'])
+        .next()
+        .unwrap_or("");
+    let candidate = match host.strip_suffix(".plex.direct") {
+        Some(rest) => rest.split('.').next().unwrap_or(rest),
+        None => host,
+    };
+    candidate
+        .replace('-', ".")
+        .parse::<std::net::Ipv4Addr>()
+        .map(|ip| ip.is_private() || ip.is_loopback())
+        .unwrap_or(false)
+}
 
-        Some(
-            resources
-                .into_iter()
-                .filter(|r| r.provides.contains("server") && !r.connections.is_empty())
-                .map(|r| {
-                    info!("Server: {} ({} connections)", r.name, r.connections.len());
-                    ServerInfo {
-                        name: r.name,
-                        access_token: r.access_token,
-                        connections: r
-                            .connections
-                            .into_iter()
-                            .map(|c| ServerConnection { uri: c.uri })
-                            .collect(),
-                    }
-                })
-                .collect(),
-        )
+// LAN Plex servers listen on the same address/port over both schemes, so for
+// a connection already identified as local this just swaps the scheme,
+// sidestepping TLS (and any untrusted-cert prompt it triggers) entirely.
+fn prefer_http(uri: String) -> String {
+    match uri.strip_prefix("https://") {
+        Some(rest) => format!("http://{}", rest),
+        None => uri,
     }
 }
 
@@ -134,6 +204,8 @@ impl PlexAccount {
 struct PlexResource {
     name: String,
     provides: String,
+    #[serde(rename = "clientIdentifier")]
+    client_identifier: String,
     #[serde(rename = "accessToken")]
     access_token: Option<String>,
     #[serde(default)]
@@ -143,4 +215,44 @@ struct PlexResource {
 #[derive(Deserialize)]
 struct PlexConnection {
     uri: String,
+    #[serde(default)]
+    relay: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_plain_rfc1918_address_as_local() {
+        assert!(is_local_uri("https://192.168.1.50:32400"));
+        assert!(is_local_uri("http://10.0.0.5:32400"));
+        assert!(!is_local_uri("https://203.0.113.10:32400"));
+    }
+
+    #[test]
+    fn detects_plex_direct_hostname_by_its_embedded_address() {
+        assert!(is_local_uri(
+            "https://192-168-1-50.3f2504e0-4f89-11d3-9a0c-0305e82c3301.plex.direct:32400"
+        ));
+        assert!(!is_local_uri(
+            "https://203-0-113-10.3f2504e0-4f89-11d3-9a0c-0305e82c3301.plex.direct:32400"
+        ));
+    }
+
+    #[test]
+    fn prefer_http_swaps_the_scheme_and_keeps_the_address_and_port() {
+        assert_eq!(
+            prefer_http("https://192-168-1-50.uuid.plex.direct:32400".to_string()),
+            "http://192-168-1-50.uuid.plex.direct:32400"
+        );
+    }
+
+    #[test]
+    fn prefer_http_leaves_a_plain_http_uri_unchanged() {
+        assert_eq!(
+            prefer_http("http://192.168.1.50:32400".to_string()),
+            "http://192.168.1.50:32400"
+        );
+    }
 }
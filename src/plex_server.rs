@@ -1,26 +1,114 @@
 use eventsource_client::{self as es, Client as EsClient, SSE};
 use futures_util::TryStreamExt;
-use log::info;
+use log::{debug, info, warn};
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
 use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
 use tokio::sync::{RwLock, mpsc};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
 
-use crate::media::{MediaInfo, MediaType, MediaUpdate, PlaybackState};
+use crate::media::{Marker, MediaInfo, MediaType, MediaUpdate, PlaybackState};
 use crate::metadata::MetadataEnricher;
-use crate::plex_account::{APP_NAME, ServerConnection};
+use crate::plex_account::ServerConnection;
+use crate::redact::redact;
+#[cfg(feature = "tray")]
+use crate::tray::TrayStatus;
 
 const SSE_RECONNECT_DELAY: Duration = Duration::from_secs(5);
-const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
 const SEEK_THRESHOLD_MS: u64 = 30_000;
+// How close a post-skip offset has to land to a marker's end to be treated
+// as that marker skip rather than an unrelated seek.
+const MARKER_SNAP_TOLERANCE_MS: u64 = 3_000;
+// After this many fully-failed passes over every connection, back off hard
+// instead of hammering a permanently-unreachable server every 5s.
+const CIRCUIT_BREAKER_THRESHOLD: u32 = 6;
+const CIRCUIT_BREAKER_DELAY: Duration = Duration::from_secs(300);
+// Consecutive failed polls before giving up on this connection and letting
+// the outer loop retry SSE from scratch.
+const POLL_FAILURE_RETRY_LIMIT: u32 = 3;
+
+// Which live-update mechanism to subscribe to for playback notifications.
+// SSE (`/:/eventsource/notifications`) is the default and works everywhere;
+// some reverse proxies buffer or drop event streams entirely, so the Plex
+// websocket (`/:/websockets/notifications`) is offered as a workaround.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NotificationTransport {
+    Sse,
+    WebSocket,
+}
 
 pub struct PlexServer {
     name: String,
     connections: Vec<ServerConnection>,
     access_token: String,
     username: Option<String>,
+    ignore_extras: bool,
+    notification_transport: NotificationTransport,
+    // 0 disables the fallback entirely
+    poll_fallback_interval_secs: u64,
+    // 0 disables the watchdog entirely
+    stale_session_check_interval_secs: u64,
+    // Serves artwork through Plex's own transcoder instead of TMDB/Jikan
+    use_plex_artwork: bool,
+    // When set, artwork URLs are rewritten to route through this local proxy
+    // instead of exposing `access_token` directly to Discord.
+    art_proxy_public_base_url: Option<String>,
+    // Some shared servers omit the `User` tag even on the owner's own
+    // session, which would otherwise filter it out entirely. When set, a
+    // username filter that matches nothing falls back to the sole active
+    // session rather than reporting nothing playing.
+    fallback_to_any_session_when_no_user: bool,
+    client_identifier: String,
     client: Client,
+    request_timeout: Duration,
+    sse_connect_timeout: Duration,
+}
+
+// Bundles the knobs that configure a server beyond its identity and
+// credentials, so `PlexServer::new` doesn't blow out its argument count
+// every time a new setting is added.
+pub struct PlexServerOptions {
+    pub ignore_extras: bool,
+    pub allow_insecure_tls: bool,
+    pub notification_transport: NotificationTransport,
+    pub poll_fallback_interval_secs: u64,
+    pub stale_session_check_interval_secs: u64,
+    pub use_plex_artwork: bool,
+    pub art_proxy_public_base_url: Option<String>,
+    pub fallback_to_any_session_when_no_user: bool,
+    pub http_timeout_secs: u64,
+    pub sse_connect_timeout_secs: u64,
+    pub client_identifier: String,
+    pub user_agent: String,
+}
+
+// Distinguishes why a `/status/sessions` request came back empty-handed, so
+// callers can react appropriately: `Network` and `Parse` are both handled
+// as ordinary failures and retried as usual, while `Auth` is surfaced as a
+// need to re-authenticate rather than keep silently retrying a dead token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlexError {
+    // The request itself failed to send, or timed out.
+    Network,
+    // Plex rejected the token (401).
+    Auth,
+    // A response came back but its body didn't parse as expected.
+    Parse,
+}
+
+// Bundles everything a session-handling call needs beyond self, so adding a
+// field doesn't blow out every call site's argument count.
+struct SessionContext<'a> {
+    uri: &'a str,
+    tx: &'a mpsc::UnboundedSender<MediaUpdate>,
+    enricher: &'a Arc<MetadataEnricher>,
+    tracker: &'a RwLock<PlaybackTracker>,
+    #[cfg(feature = "tray")]
+    status_tx: &'a mpsc::UnboundedSender<TrayStatus>,
 }
 
 #[derive(Default)]
@@ -77,16 +165,28 @@ impl PlexServer {
         connections: Vec<ServerConnection>,
         access_token: String,
         username: Option<String>,
+        opts: PlexServerOptions,
     ) -> Self {
         Self {
             name,
             connections,
             access_token,
             username,
+            ignore_extras: opts.ignore_extras,
+            notification_transport: opts.notification_transport,
+            poll_fallback_interval_secs: opts.poll_fallback_interval_secs,
+            stale_session_check_interval_secs: opts.stale_session_check_interval_secs,
+            use_plex_artwork: opts.use_plex_artwork,
+            art_proxy_public_base_url: opts.art_proxy_public_base_url,
+            fallback_to_any_session_when_no_user: opts.fallback_to_any_session_when_no_user,
             client: Client::builder()
-                .user_agent("PresenceForPlex/1.0")
+                .user_agent(opts.user_agent)
+                .danger_accept_invalid_certs(opts.allow_insecure_tls)
                 .build()
                 .expect("HTTP client"),
+            request_timeout: Duration::from_secs(opts.http_timeout_secs),
+            sse_connect_timeout: Duration::from_secs(opts.sse_connect_timeout_secs),
+            client_identifier: opts.client_identifier,
         }
     }
 
@@ -94,122 +194,570 @@ impl PlexServer {
         self,
         tx: mpsc::UnboundedSender<MediaUpdate>,
         enricher: Arc<MetadataEnricher>,
+        #[cfg(feature = "tray")] status_tx: mpsc::UnboundedSender<TrayStatus>,
+        // Shared across every monitored server, for the heartbeat log to
+        // report how many are currently reachable.
+        connected_servers: Arc<AtomicUsize>,
     ) {
         info!("Monitoring server: {}", self.name);
+        let mut consecutive_failures = 0u32;
+        let mut was_connected = false;
+        #[cfg(feature = "tray")]
+        let mut reported_unreachable = false;
         loop {
+            let mut any_connected = false;
             for conn in &self.connections {
-                self.try_connection(&conn.uri, &tx, &enricher).await;
+                if self
+                    .try_connection(
+                        &conn.uri,
+                        &tx,
+                        &enricher,
+                        #[cfg(feature = "tray")]
+                        &status_tx,
+                    )
+                    .await
+                {
+                    any_connected = true;
+                }
+            }
+
+            if any_connected != was_connected {
+                if any_connected {
+                    connected_servers.fetch_add(1, Ordering::Relaxed);
+                } else {
+                    connected_servers.fetch_sub(1, Ordering::Relaxed);
+                }
+                was_connected = any_connected;
             }
-            tokio::time::sleep(SSE_RECONNECT_DELAY).await;
+
+            if any_connected {
+                consecutive_failures = 0;
+                #[cfg(feature = "tray")]
+                if reported_unreachable {
+                    reported_unreachable = false;
+                    let _ = status_tx.send(TrayStatus::Idle);
+                }
+            } else {
+                consecutive_failures += 1;
+            }
+
+            let delay = if consecutive_failures >= CIRCUIT_BREAKER_THRESHOLD {
+                if consecutive_failures == CIRCUIT_BREAKER_THRESHOLD {
+                    warn!(
+                        "Server {} unreachable on all {} connection(s) after {} attempts, backing off to {}s retries",
+                        self.name,
+                        self.connections.len(),
+                        consecutive_failures,
+                        CIRCUIT_BREAKER_DELAY.as_secs()
+                    );
+                    #[cfg(feature = "tray")]
+                    {
+                        reported_unreachable = true;
+                        let _ = status_tx.send(TrayStatus::ServerUnreachable);
+                    }
+                }
+                CIRCUIT_BREAKER_DELAY
+            } else {
+                SSE_RECONNECT_DELAY
+            };
+            tokio::time::sleep(delay).await;
         }
     }
 
+    // Returns whether the stream ever successfully opened.
     async fn try_connection(
         &self,
         uri: &str,
         tx: &mpsc::UnboundedSender<MediaUpdate>,
         enricher: &Arc<MetadataEnricher>,
-    ) {
+        #[cfg(feature = "tray")] status_tx: &mpsc::UnboundedSender<TrayStatus>,
+    ) -> bool {
+        match self.notification_transport {
+            NotificationTransport::Sse => {
+                self.try_connection_sse(
+                    uri,
+                    tx,
+                    enricher,
+                    #[cfg(feature = "tray")]
+                    status_tx,
+                )
+                .await
+            }
+            NotificationTransport::WebSocket => {
+                self.try_connection_ws(
+                    uri,
+                    tx,
+                    enricher,
+                    #[cfg(feature = "tray")]
+                    status_tx,
+                )
+                .await
+            }
+        }
+    }
+
+    // Returns whether the SSE stream ever successfully opened.
+    async fn try_connection_sse(
+        &self,
+        uri: &str,
+        tx: &mpsc::UnboundedSender<MediaUpdate>,
+        enricher: &Arc<MetadataEnricher>,
+        #[cfg(feature = "tray")] status_tx: &mpsc::UnboundedSender<TrayStatus>,
+    ) -> bool {
         let url = format!("{}/:/eventsource/notifications?filters=playing", uri);
         let Ok(builder) = es::ClientBuilder::for_url(&url) else {
-            return;
+            return false;
         };
         let Ok(builder) = builder.header("Accept", "text/event-stream") else {
-            return;
+            return false;
         };
         let Ok(builder) = builder.header("X-Plex-Token", &self.access_token) else {
-            return;
+            return false;
         };
-        let Ok(builder) = builder.header("X-Plex-Client-Identifier", APP_NAME) else {
-            return;
+        let Ok(builder) = builder.header("X-Plex-Client-Identifier", &self.client_identifier)
+        else {
+            return false;
         };
         let client = builder.build();
 
         let mut stream = Box::pin(client.stream());
         let tracker = RwLock::new(PlaybackTracker::default());
         let mut opened = false;
+        let ctx = SessionContext {
+            uri,
+            tx,
+            enricher,
+            tracker: &tracker,
+            #[cfg(feature = "tray")]
+            status_tx,
+        };
 
-        while let Ok(Some(event)) = stream.try_next().await {
-            match event {
-                SSE::Connected(_) => {
-                    opened = true;
-                    info!("SSE connected: {}", uri);
-                }
-                SSE::Event(ev) => {
-                    self.handle_message(&ev.data, uri, tx, enricher, &tracker)
-                        .await
+        let first = match tokio::time::timeout(self.sse_connect_timeout, stream.try_next()).await {
+            Ok(Ok(Some(event))) => event,
+            Ok(Ok(None)) | Ok(Err(_)) => return false,
+            Err(_) if self.poll_fallback_interval_secs > 0 => {
+                warn!(
+                    "SSE did not connect to {} within {}s, falling back to polling /status/sessions every {}s",
+                    redact(uri),
+                    self.sse_connect_timeout.as_secs(),
+                    self.poll_fallback_interval_secs
+                );
+                return self.poll_sessions(&ctx).await;
+            }
+            Err(_) => return false,
+        };
+        self.handle_sse_event(first, &ctx, &mut opened).await;
+
+        if self.stale_session_check_interval_secs == 0 {
+            while let Ok(Some(event)) = stream.try_next().await {
+                self.handle_sse_event(event, &ctx, &mut opened).await;
+            }
+        } else {
+            let mut watchdog =
+                tokio::time::interval(Duration::from_secs(self.stale_session_check_interval_secs));
+            watchdog.tick().await;
+            loop {
+                tokio::select! {
+                    event = stream.try_next() => {
+                        match event {
+                            Ok(Some(event)) => self.handle_sse_event(event, &ctx, &mut opened).await,
+                            _ => break,
+                        }
+                    }
+                    _ = watchdog.tick() => {
+                        let tracked = ctx.tracker.read().await.info.as_ref().and_then(|i| i.rating_key.clone());
+                        self.clear_if_stale(&ctx, tracked).await;
+                    }
                 }
-                SSE::Comment(_) => {}
             }
         }
 
         if opened && tracker.write().await.clear_if_server(uri) {
-            let _ = tx.send(MediaUpdate::Stopped);
+            let _ = tx.send(MediaUpdate::Stopped(self.name.clone()));
         }
+        opened
     }
 
-    async fn handle_message(
+    // Returns whether the websocket ever successfully opened. Mirrors
+    // `try_connection_sse`'s structure, except that a connect timeout just
+    // fails the connection (there is no polling fallback here) and events
+    // come from websocket text frames, each already the bare notification
+    // payload `handle_message` expects, with no `SSE::Connected`/
+    // `SSE::Comment` wrapper to unwrap first.
+    async fn try_connection_ws(
         &self,
-        data: &str,
         uri: &str,
         tx: &mpsc::UnboundedSender<MediaUpdate>,
         enricher: &Arc<MetadataEnricher>,
-        tracker: &RwLock<PlaybackTracker>,
-    ) {
-        let Ok(notif) = serde_json::from_str::<SseNotification>(data) else {
-            return;
+        #[cfg(feature = "tray")] status_tx: &mpsc::UnboundedSender<TrayStatus>,
+    ) -> bool {
+        let Ok(request) = websocket_request(uri, &self.access_token, &self.client_identifier)
+        else {
+            return false;
         };
-        let Some(playing) = notif.play_session_state else {
-            return;
+        let connect = tokio_tungstenite::connect_async(request);
+        let mut stream = match tokio::time::timeout(self.sse_connect_timeout, connect).await {
+            Ok(Ok((stream, _))) => stream,
+            Ok(Err(e)) => {
+                if is_unauthorized(&e) {
+                    warn!(
+                        "WebSocket handshake to {} rejected as unauthorized, token may be revoked",
+                        redact(uri)
+                    );
+                    #[cfg(feature = "tray")]
+                    let _ = status_tx.send(TrayStatus::ReauthRequired);
+                }
+                return false;
+            }
+            Err(_) => return false,
+        };
+
+        let tracker = RwLock::new(PlaybackTracker::default());
+        let opened = true;
+        let ctx = SessionContext {
+            uri,
+            tx,
+            enricher,
+            tracker: &tracker,
+            #[cfg(feature = "tray")]
+            status_tx,
         };
+        info!("WebSocket connected: {}", redact(uri));
+        self.sync_initial_session(&ctx).await;
 
-        if playing.state == "stopped" {
-            let mut t = tracker.write().await;
-            if t.info.is_some() {
-                *t = PlaybackTracker::default();
-                let _ = tx.send(MediaUpdate::Stopped);
+        if self.stale_session_check_interval_secs == 0 {
+            while let Ok(Some(msg)) = stream.try_next().await {
+                self.handle_ws_message(msg, &ctx).await;
+            }
+        } else {
+            let mut watchdog =
+                tokio::time::interval(Duration::from_secs(self.stale_session_check_interval_secs));
+            watchdog.tick().await;
+            loop {
+                tokio::select! {
+                    msg = stream.try_next() => {
+                        match msg {
+                            Ok(Some(msg)) => self.handle_ws_message(msg, &ctx).await,
+                            _ => break,
+                        }
+                    }
+                    _ = watchdog.tick() => {
+                        let tracked = ctx.tracker.read().await.info.as_ref().and_then(|i| i.rating_key.clone());
+                        self.clear_if_stale(&ctx, tracked).await;
+                    }
+                }
             }
-            return;
         }
 
-        let state = match playing.state.as_str() {
-            "playing" => PlaybackState::Playing,
-            "paused" => PlaybackState::Paused,
-            "buffering" => PlaybackState::Buffering,
-            _ => return,
-        };
-        let offset = playing.view_offset.unwrap_or(0);
+        if opened && tracker.write().await.clear_if_server(uri) {
+            let _ = tx.send(MediaUpdate::Stopped(self.name.clone()));
+        }
+        opened
+    }
 
-        {
-            let mut t = tracker.write().await;
-            if t.info.as_ref().and_then(|i| i.rating_key.as_deref()) == Some(&playing.rating_key) {
-                if t.is_duplicate(&playing.rating_key, state, offset) {
-                    return;
+    async fn handle_ws_message(&self, msg: Message, ctx: &SessionContext<'_>) {
+        if let Message::Text(text) = msg {
+            debug!("WebSocket event: {}", redact(&text));
+            self.handle_message(&text, ctx).await;
+        }
+    }
+
+    async fn handle_sse_event(&self, event: SSE, ctx: &SessionContext<'_>, opened: &mut bool) {
+        match event {
+            SSE::Connected(_) => {
+                let already_opened = *opened;
+                *opened = true;
+                info!("SSE connected: {}", redact(ctx.uri));
+                // If we connect mid-watch (e.g. the app just started) while
+                // paused, no SSE notification fires until playback resumes,
+                // so presence would stay empty until then. Check once for an
+                // already-active session instead of waiting for one.
+                if !already_opened {
+                    self.sync_initial_session(ctx).await;
                 }
-                t.update(state, offset);
-                if let Some(ref info) = t.info {
-                    let _ = tx.send(MediaUpdate::Playing(Box::new(info.clone())));
+            }
+            SSE::Event(ev) => {
+                debug!("SSE event: {}", redact(&ev.data));
+                self.handle_message(&ev.data, ctx).await
+            }
+            SSE::Comment(_) => {}
+        }
+    }
+
+    async fn handle_message(&self, data: &str, ctx: &SessionContext<'_>) {
+        let (rating_key, state, offset) = match parse_sse_notification(data) {
+            SseOutcome::Ignored => return,
+            SseOutcome::Stopped => {
+                let mut t = ctx.tracker.write().await;
+                if t.info.is_some() {
+                    *t = PlaybackTracker::default();
+                    let _ = ctx.tx.send(MediaUpdate::Stopped(self.name.clone()));
                 }
                 return;
             }
+            SseOutcome::Update {
+                rating_key,
+                state,
+                offset,
+            } => (rating_key, state, offset),
+        };
+
+        if !self.update_or_defer(&rating_key, state, offset, ctx).await {
+            return;
         }
 
+        // This is a different session than the one we're tracking. Remember it in
+        // case it turns out to be filtered out below, so we can check whether the
+        // one we're still showing has gone stale (e.g. its "stopped" event was lost).
+        let previously_tracked = ctx
+            .tracker
+            .read()
+            .await
+            .info
+            .as_ref()
+            .and_then(|info| info.rating_key.clone());
+
         // For server owners, verify this session belongs to them
-        if self.username.is_some() && !self.is_own_session(uri, &playing.rating_key).await {
+        if self.username.is_some() && !self.is_own_session(ctx.uri, &rating_key).await {
+            self.clear_if_stale(ctx, previously_tracked).await;
+            return;
+        }
+
+        if !self.fetch_and_apply(&rating_key, state, offset, ctx).await {
+            self.clear_if_stale(ctx, previously_tracked).await;
+        }
+    }
+
+    // A playing notification for a different, filtered-out session arrived while we
+    // were still showing an earlier one. If that earlier session's own "stopped"
+    // notification was dropped, it's no longer in `/status/sessions` either, so
+    // clear it rather than let it linger as ghost presence.
+    async fn clear_if_stale(&self, ctx: &SessionContext<'_>, tracked_rating_key: Option<String>) {
+        let Some(rating_key) = tracked_rating_key else {
             return;
+        };
+        if self.session_exists(ctx.uri, &rating_key).await {
+            return;
+        }
+        let mut t = ctx.tracker.write().await;
+        if t.clear_if_server(ctx.uri) {
+            let _ = ctx.tx.send(MediaUpdate::Stopped(self.name.clone()));
+        }
+    }
+
+    // Handles a session we're already tracking, or defers to the caller to
+    // resolve full metadata for a session we've never seen. Returns whether
+    // the caller needs to go on and do that.
+    async fn update_or_defer(
+        &self,
+        rating_key: &str,
+        state: PlaybackState,
+        offset: u64,
+        ctx: &SessionContext<'_>,
+    ) -> bool {
+        let mut t = ctx.tracker.write().await;
+        if let Some(info) = t.info.as_ref()
+            && info.rating_key.as_deref() == Some(rating_key)
+        {
+            let offset = Self::snap_to_marker_boundary(&info.markers, info.view_offset_ms, offset);
+            if t.is_duplicate(rating_key, state, offset) {
+                return false;
+            }
+            t.update(state, offset);
+            if let Some(ref info) = t.info {
+                let _ = ctx.tx.send(MediaUpdate::Playing(Box::new(info.clone())));
+            }
+            return false;
+        }
+
+        // A different session than the one we're tracking. If the owner has
+        // this session playing on multiple devices (e.g. phone + TV), don't
+        // let a lower-priority one (a forgotten paused phone) steal focus
+        // from what's actively playing.
+        if let Some(ref info) = t.info
+            && info.state.priority() > state.priority()
+        {
+            return false;
         }
 
+        true
+    }
+
+    // Returns whether metadata was resolved and applied.
+    async fn fetch_and_apply(
+        &self,
+        rating_key: &str,
+        state: PlaybackState,
+        offset: u64,
+        ctx: &SessionContext<'_>,
+    ) -> bool {
         let Some(mut info) = self
-            .fetch_metadata(uri, &playing.rating_key, state, offset)
+            .fetch_metadata(ctx.uri, rating_key, state, offset)
             .await
         else {
-            return;
+            return false;
         };
         info!("Now playing: {} ({:?})", info.title, info.state);
-        enricher.enrich(&mut info).await;
+        ctx.enricher.enrich(&mut info, self.use_plex_artwork).await;
+
+        if let Some(size) = self.watch_together_size(ctx.uri, rating_key).await {
+            info.party_size = Some(size);
+            info.party_max = Some(size);
+        }
+
+        if let Some(viewers) = self.group_viewers(ctx.uri, rating_key).await {
+            info.is_group = true;
+            info.group_name = (!viewers.is_empty()).then(|| viewers.join(", "));
+        }
+
+        info.playback_method = self
+            .playback_method(ctx.uri, rating_key)
+            .await
+            .map(str::to_string);
+        info.device = self.device(ctx.uri, rating_key).await;
+
+        ctx.tracker.write().await.set(info.clone(), ctx.uri);
+        let _ = ctx.tx.send(MediaUpdate::Playing(Box::new(info)));
+        true
+    }
+
+    // Polls `/status/sessions` on an interval as a fallback for setups where
+    // a reverse proxy breaks the SSE stream. Returns once too many
+    // consecutive *errors* (not just "nothing playing", which is a normal
+    // outcome) happen in a row, so the caller retries SSE from scratch. Stops
+    // immediately on `PlexError::Auth` instead of burning through the retry
+    // limit against a token that's never going to start working again.
+    async fn poll_sessions(&self, ctx: &SessionContext<'_>) -> bool {
+        let interval = Duration::from_secs(self.poll_fallback_interval_secs);
+        let mut any_success = false;
+        let mut consecutive_failures = 0u32;
+
+        loop {
+            match self.poll_session(ctx.uri).await {
+                Ok(session) => {
+                    any_success = true;
+                    consecutive_failures = 0;
+                    if let Some((rating_key, state, offset)) = session {
+                        if self.update_or_defer(&rating_key, state, offset, ctx).await {
+                            self.fetch_and_apply(&rating_key, state, offset, ctx).await;
+                        }
+                    } else if ctx.tracker.write().await.clear_if_server(ctx.uri) {
+                        let _ = ctx.tx.send(MediaUpdate::Stopped(self.name.clone()));
+                    }
+                }
+                Err(PlexError::Auth) => {
+                    warn!(
+                        "Polling {} rejected as unauthorized, token may be revoked",
+                        redact(ctx.uri)
+                    );
+                    #[cfg(feature = "tray")]
+                    let _ = ctx.status_tx.send(TrayStatus::ReauthRequired);
+                    return any_success;
+                }
+                Err(_) => {
+                    consecutive_failures += 1;
+                    if ctx.tracker.write().await.clear_if_server(ctx.uri) {
+                        let _ = ctx.tx.send(MediaUpdate::Stopped(self.name.clone()));
+                    }
+                    if consecutive_failures >= POLL_FAILURE_RETRY_LIMIT {
+                        return any_success;
+                    }
+                }
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    // Checks once for a session already in progress when the SSE stream
+    // opens, so a session left paused before the app started shows up
+    // immediately. `update_or_defer`'s own duplicate detection keeps this
+    // from double-reporting once the first real SSE notification arrives.
+    async fn sync_initial_session(&self, ctx: &SessionContext<'_>) {
+        match self.poll_session(ctx.uri).await {
+            Ok(Some((rating_key, state, offset))) => {
+                if self.update_or_defer(&rating_key, state, offset, ctx).await {
+                    self.fetch_and_apply(&rating_key, state, offset, ctx).await;
+                }
+            }
+            Ok(None) => {}
+            Err(PlexError::Auth) => {
+                warn!(
+                    "Initial session check on {} rejected as unauthorized, token may be revoked",
+                    redact(ctx.uri)
+                );
+                #[cfg(feature = "tray")]
+                let _ = ctx.status_tx.send(TrayStatus::ReauthRequired);
+            }
+            Err(_) => {}
+        }
+    }
+
+    // Ok(None) means the request succeeded but there's simply nothing
+    // playing (e.g. for this user); that's a normal outcome, distinct from
+    // the error cases callers should react to differently (see `PlexError`).
+    async fn poll_session(
+        &self,
+        uri: &str,
+    ) -> Result<Option<(String, PlaybackState, u64)>, PlexError> {
+        let resp = self
+            .client
+            .get(format!("{}/status/sessions", uri))
+            .header("Accept", "application/json")
+            .header("X-Plex-Token", &self.access_token)
+            .header("X-Plex-Client-Identifier", &self.client_identifier)
+            .timeout(self.request_timeout)
+            .send()
+            .await
+            .map_err(|_| PlexError::Network)?;
+
+        if resp.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(PlexError::Auth);
+        }
+
+        let sessions: SessionsResponse = resp.json().await.map_err(|_| PlexError::Parse)?;
+        let Some(session) = Self::select_session(
+            sessions.media_container.metadata,
+            self.username.as_deref(),
+            self.fallback_to_any_session_when_no_user,
+        ) else {
+            return Ok(None);
+        };
+
+        let Some(rating_key) = session.rating_key else {
+            return Ok(None);
+        };
+        let Some(player) = session.player else {
+            return Ok(None);
+        };
+        let state = match player.state.as_str() {
+            "playing" => PlaybackState::Playing,
+            "paused" => PlaybackState::Paused,
+            "buffering" => PlaybackState::Buffering,
+            _ => return Ok(None),
+        };
+        Ok(Some((rating_key, state, session.view_offset.unwrap_or(0))))
+    }
 
-        tracker.write().await.set(info.clone(), uri);
-        let _ = tx.send(MediaUpdate::Playing(Box::new(info)));
+    // Filters sessions down to the owner's by username. Some shared servers
+    // omit the `User` tag even on the owner's own session, which would
+    // otherwise make this match nothing; when `fallback_to_any` is set and
+    // that happens to be the sole active session, use it anyway rather than
+    // reporting nothing playing.
+    fn select_session(
+        mut sessions: Vec<SessionMetadata>,
+        username: Option<&str>,
+        fallback_to_any: bool,
+    ) -> Option<SessionMetadata> {
+        let total = sessions.len();
+        let idx = sessions.iter().position(|m| {
+            username
+                .is_none_or(|username| m.user.as_ref().map(|u| u.title.as_str()) == Some(username))
+        });
+        match idx {
+            Some(i) => Some(sessions.swap_remove(i)),
+            None if fallback_to_any && username.is_some() && total == 1 => sessions.pop(),
+            None => None,
+        }
     }
 
     async fn is_own_session(&self, uri: &str, rating_key: &str) -> bool {
@@ -222,8 +770,8 @@ impl PlexServer {
             .get(format!("{}/status/sessions", uri))
             .header("Accept", "application/json")
             .header("X-Plex-Token", &self.access_token)
-            .header("X-Plex-Client-Identifier", APP_NAME)
-            .timeout(REQUEST_TIMEOUT)
+            .header("X-Plex-Client-Identifier", &self.client_identifier)
+            .timeout(self.request_timeout)
             .send()
             .await
         else {
@@ -238,13 +786,184 @@ impl PlexServer {
         let Ok(sessions) = resp.json::<SessionsResponse>().await else {
             return false;
         };
+        let sessions = sessions.media_container.metadata;
 
-        sessions.media_container.metadata.iter().any(|m| {
+        if sessions.iter().any(|m| {
             m.rating_key.as_deref() == Some(rating_key)
                 && m.user.as_ref().map(|u| &u.title) == Some(username)
+        }) {
+            return true;
+        }
+
+        self.fallback_to_any_session_when_no_user
+            && sessions.len() == 1
+            && sessions[0].rating_key.as_deref() == Some(rating_key)
+    }
+
+    // Defaults to true on a failed check, since we'd rather risk briefly
+    // keeping a genuinely-stopped session than clear a still-playing one
+    // because of a transient network error.
+    async fn session_exists(&self, uri: &str, rating_key: &str) -> bool {
+        let Ok(resp) = self
+            .client
+            .get(format!("{}/status/sessions", uri))
+            .header("Accept", "application/json")
+            .header("X-Plex-Token", &self.access_token)
+            .header("X-Plex-Client-Identifier", &self.client_identifier)
+            .timeout(self.request_timeout)
+            .send()
+            .await
+        else {
+            return true;
+        };
+
+        let Ok(sessions) = resp.json::<SessionsResponse>().await else {
+            return true;
+        };
+
+        sessions
+            .media_container
+            .metadata
+            .iter()
+            .any(|m| m.rating_key.as_deref() == Some(rating_key))
+    }
+
+    // Counts how many current sessions are watching this same item, e.g. a
+    // Plex Watch Together group. Returns None for a solo session.
+    async fn watch_together_size(&self, uri: &str, rating_key: &str) -> Option<u32> {
+        let resp = self
+            .client
+            .get(format!("{}/status/sessions", uri))
+            .header("Accept", "application/json")
+            .header("X-Plex-Token", &self.access_token)
+            .header("X-Plex-Client-Identifier", &self.client_identifier)
+            .timeout(self.request_timeout)
+            .send()
+            .await
+            .ok()?;
+
+        let sessions: SessionsResponse = resp.json().await.ok()?;
+        Self::count_watching(&sessions.media_container.metadata, rating_key)
+    }
+
+    fn count_watching(sessions: &[SessionMetadata], rating_key: &str) -> Option<u32> {
+        let count = sessions
+            .iter()
+            .filter(|m| m.rating_key.as_deref() == Some(rating_key))
+            .count() as u32;
+
+        (count > 1).then_some(count)
+    }
+
+    // The other participants' usernames in a Watch Together group sharing
+    // this rating_key, for the `{is_group}`/`{group_name}` placeholders.
+    // Distinct from `watch_together_size`/`count_watching`, which only care
+    // about the count. None for a solo session.
+    async fn group_viewers(&self, uri: &str, rating_key: &str) -> Option<Vec<String>> {
+        let resp = self
+            .client
+            .get(format!("{}/status/sessions", uri))
+            .header("Accept", "application/json")
+            .header("X-Plex-Token", &self.access_token)
+            .header("X-Plex-Client-Identifier", &self.client_identifier)
+            .timeout(self.request_timeout)
+            .send()
+            .await
+            .ok()?;
+
+        let sessions: SessionsResponse = resp.json().await.ok()?;
+        Self::resolve_group_viewers(
+            &sessions.media_container.metadata,
+            rating_key,
+            self.username.as_deref(),
+        )
+    }
+
+    fn resolve_group_viewers(
+        sessions: &[SessionMetadata],
+        rating_key: &str,
+        own_username: Option<&str>,
+    ) -> Option<Vec<String>> {
+        let matching: Vec<&SessionMetadata> = sessions
+            .iter()
+            .filter(|m| m.rating_key.as_deref() == Some(rating_key))
+            .collect();
+        if matching.len() <= 1 {
+            return None;
+        }
+        Some(
+            matching
+                .iter()
+                .filter_map(|m| m.user.as_ref().map(|u| u.title.clone()))
+                .filter(|name| Some(name.as_str()) != own_username)
+                .collect(),
+        )
+    }
+
+    // Resolves whether this session is transcoding or direct playing, for the
+    // {playback_method} placeholder. None if the session can't be found.
+    async fn playback_method(&self, uri: &str, rating_key: &str) -> Option<&'static str> {
+        let resp = self
+            .client
+            .get(format!("{}/status/sessions", uri))
+            .header("Accept", "application/json")
+            .header("X-Plex-Token", &self.access_token)
+            .header("X-Plex-Client-Identifier", &self.client_identifier)
+            .timeout(self.request_timeout)
+            .send()
+            .await
+            .ok()?;
+
+        let sessions: SessionsResponse = resp.json().await.ok()?;
+        Self::resolve_playback_method(&sessions.media_container.metadata, rating_key)
+    }
+
+    fn resolve_playback_method(
+        sessions: &[SessionMetadata],
+        rating_key: &str,
+    ) -> Option<&'static str> {
+        let session = sessions
+            .iter()
+            .find(|m| m.rating_key.as_deref() == Some(rating_key))?;
+
+        if session.transcode_session.is_some() {
+            return Some("Transcode");
+        }
+        let decision = session.media.first()?.parts.first()?.decision.as_deref()?;
+        Some(if decision == "transcode" {
+            "Transcode"
+        } else {
+            "Direct Play"
         })
     }
 
+    // Resolves the playing device's product name for the {device} placeholder.
+    // None if the session couldn't be found in /status/sessions.
+    async fn device(&self, uri: &str, rating_key: &str) -> Option<String> {
+        let resp = self
+            .client
+            .get(format!("{}/status/sessions", uri))
+            .header("Accept", "application/json")
+            .header("X-Plex-Token", &self.access_token)
+            .header("X-Plex-Client-Identifier", &self.client_identifier)
+            .timeout(self.request_timeout)
+            .send()
+            .await
+            .ok()?;
+
+        let sessions: SessionsResponse = resp.json().await.ok()?;
+        Self::resolve_device(&sessions.media_container.metadata, rating_key)
+    }
+
+    fn resolve_device(sessions: &[SessionMetadata], rating_key: &str) -> Option<String> {
+        let player = sessions
+            .iter()
+            .find(|m| m.rating_key.as_deref() == Some(rating_key))?
+            .player
+            .as_ref()?;
+        player.product.clone().or_else(|| player.platform.clone())
+    }
+
     async fn fetch_metadata(
         &self,
         uri: &str,
@@ -257,8 +976,8 @@ impl PlexServer {
             .get(format!("{}/library/metadata/{}", uri, rating_key))
             .header("Accept", "application/json")
             .header("X-Plex-Token", &self.access_token)
-            .header("X-Plex-Client-Identifier", APP_NAME)
-            .timeout(REQUEST_TIMEOUT)
+            .header("X-Plex-Client-Identifier", &self.client_identifier)
+            .timeout(self.request_timeout)
             .send()
             .await
             .ok()?;
@@ -266,11 +985,40 @@ impl PlexServer {
         let meta_resp: MetadataResponse = resp.json().await.ok()?;
         let meta = meta_resp.media_container.metadata.into_iter().next()?;
 
+        if self.ignore_extras && meta.extra_type.is_some() {
+            info!("Ignoring extra/trailer session: {}", meta.title);
+            return None;
+        }
+
+        let thumb = meta.thumb.clone();
         let mut info = Self::parse_metadata(meta, rating_key, state, view_offset)?;
+        info.server = self.name.clone();
+        if self.use_plex_artwork
+            && let Some(thumb) = thumb
+        {
+            info.art_url = Some(self.plex_artwork_url(uri, &thumb));
+        }
         self.enrich_external_ids(uri, &mut info).await;
         Some(info)
     }
 
+    // Has Plex itself transcode the session's poster to a size Discord will
+    // accept, instead of TMDB/Jikan's artwork. Only useful when `uri` is
+    // reachable from wherever Discord renders the embed, i.e. not a purely
+    // local connection.
+    fn plex_artwork_url(&self, uri: &str, thumb: &str) -> String {
+        let full_thumb = format!("{uri}{thumb}");
+        let target = utf8_percent_encode(&full_thumb, NON_ALPHANUMERIC);
+        let direct = format!(
+            "{uri}/photo/:/transcode?width=500&height=500&minSize=1&upscale=1&url={target}&X-Plex-Token={token}",
+            token = self.access_token
+        );
+        match &self.art_proxy_public_base_url {
+            Some(base) => crate::art_proxy::public_url(base, &direct),
+            None => direct,
+        }
+    }
+
     async fn enrich_external_ids(&self, uri: &str, info: &mut MediaInfo) {
         let key = match info.media_type {
             MediaType::Episode => info.grandparent_key.as_deref(),
@@ -284,8 +1032,8 @@ impl PlexServer {
             .get(format!("{}{}", uri, key))
             .header("Accept", "application/json")
             .header("X-Plex-Token", &self.access_token)
-            .header("X-Plex-Client-Identifier", APP_NAME)
-            .timeout(REQUEST_TIMEOUT)
+            .header("X-Plex-Client-Identifier", &self.client_identifier)
+            .timeout(self.request_timeout)
             .send()
             .await
             .ok()
@@ -310,19 +1058,74 @@ impl PlexServer {
 
         if info.media_type == MediaType::Episode {
             info.genres = item.genres.into_iter().map(|g| g.tag).collect();
+            info.network = item.studio;
+
+            if let Some(parent_key) = info.parent_key.clone() {
+                info.episode_total = self.fetch_leaf_count(uri, &parent_key).await;
+                if let Some(episode) = info.episode {
+                    info.next_title = self
+                        .fetch_next_episode_title(uri, &parent_key, episode)
+                        .await;
+                }
+            }
         }
     }
 
-    fn parse_metadata(
-        meta: ItemMetadata,
-        rating_key: &str,
-        state: PlaybackState,
-        view_offset: u64,
-    ) -> Option<MediaInfo> {
-        let media_type = match meta.media_type.as_str() {
-            "movie" => MediaType::Movie,
-            "episode" => MediaType::Episode,
+    // The season's `leafCount` is its total episode count, used to render
+    // e.g. "Episode 4 of 10" via `{episode_total}`.
+    async fn fetch_leaf_count(&self, uri: &str, key: &str) -> Option<u32> {
+        let resp = self
+            .client
+            .get(format!("{}{}", uri, key))
+            .header("Accept", "application/json")
+            .header("X-Plex-Token", &self.access_token)
+            .header("X-Plex-Client-Identifier", &self.client_identifier)
+            .timeout(self.request_timeout)
+            .send()
+            .await
+            .ok()?;
+        let meta: MetadataResponse = resp.json().await.ok()?;
+        meta.media_container.metadata.into_iter().next()?.leaf_count
+    }
+
+    // Looks up the season's episode list via its `/children` endpoint and
+    // returns the title of the one right after `current_episode`, or None
+    // for the season finale (or if Plex doesn't report one).
+    async fn fetch_next_episode_title(
+        &self,
+        uri: &str,
+        season_key: &str,
+        current_episode: u32,
+    ) -> Option<String> {
+        let resp = self
+            .client
+            .get(format!("{}{}/children", uri, season_key))
+            .header("Accept", "application/json")
+            .header("X-Plex-Token", &self.access_token)
+            .header("X-Plex-Client-Identifier", &self.client_identifier)
+            .timeout(self.request_timeout)
+            .send()
+            .await
+            .ok()?;
+        let meta: MetadataResponse = resp.json().await.ok()?;
+        meta.media_container
+            .metadata
+            .into_iter()
+            .find(|ep| ep.index == Some(current_episode + 1))
+            .map(|ep| ep.title)
+    }
+
+    fn parse_metadata(
+        meta: ItemMetadata,
+        rating_key: &str,
+        state: PlaybackState,
+        view_offset: u64,
+    ) -> Option<MediaInfo> {
+        let media_type = match meta.media_type.as_str() {
+            "movie" => MediaType::Movie,
+            "episode" => MediaType::Episode,
             "track" => MediaType::Track,
+            "clip" => MediaType::Clip,
             _ => return None,
         };
 
@@ -333,6 +1136,40 @@ impl PlexServer {
             )
         });
 
+        let streams = meta
+            .media
+            .first()
+            .and_then(|m| m.parts.first())
+            .map(|p| p.streams.as_slice())
+            .unwrap_or(&[]);
+        let audio_lang = Self::selected_stream_language(streams, STREAM_TYPE_AUDIO);
+        let sub_lang = Self::selected_stream_language(streams, STREAM_TYPE_SUBTITLE);
+
+        let (composer, work) = if media_type == MediaType::Track {
+            let tagged_composer = (!meta.composers.is_empty()).then(|| {
+                meta.composers
+                    .iter()
+                    .map(|c| c.tag.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            });
+            (
+                tagged_composer.or_else(|| meta.parent_studio.clone()),
+                meta.original_title.clone(),
+            )
+        } else {
+            (None, None)
+        };
+
+        // Live sessions either say so outright, or give themselves away with
+        // no meaningful duration alongside a channel (grandparent) title,
+        // unlike a normal episode which always has both.
+        let is_live = meta.live.is_some_and(|live| live != 0)
+            || (media_type == MediaType::Episode
+                && meta.duration.unwrap_or(0) == 0
+                && meta.grandparent_title.is_some());
+        let channel = is_live.then(|| meta.grandparent_title.clone()).flatten();
+
         Some(MediaInfo {
             title: meta.title,
             media_type,
@@ -341,8 +1178,26 @@ impl PlexServer {
             episode: meta.index,
             artist: meta.grandparent_title,
             album: meta.parent_title,
+            track_number: if media_type == MediaType::Track {
+                meta.index
+            } else {
+                None
+            },
+            track_total: if media_type == MediaType::Track {
+                meta.leaf_count
+            } else {
+                None
+            },
+            episode_total: None,
             year: meta.year,
+            original_air_date: meta.originally_available_at,
+            rating: meta.content_rating,
             genres: meta.genres.into_iter().map(|g| g.tag).collect(),
+            directors: meta.directors.into_iter().map(|d| d.tag).collect(),
+            studio: meta.studio,
+            network: None,
+            critic_rating: meta.rating,
+            audience_rating: meta.audience_rating,
             duration_ms: meta.duration.unwrap_or(0),
             view_offset_ms: view_offset,
             state,
@@ -351,10 +1206,67 @@ impl PlexServer {
             mal_id: None,
             art_url: None,
             rating_key: Some(rating_key.to_string()),
+            guid: meta.guid,
+            // Filled in by the caller, which knows which server this came from.
+            server: String::new(),
             grandparent_key: meta.grandparent_key,
+            parent_key: meta.parent_key,
             key: meta.key,
+            markers: meta
+                .markers
+                .into_iter()
+                .map(|m| Marker {
+                    marker_type: m.marker_type,
+                    start_ms: m.start_time_offset,
+                    end_ms: m.end_time_offset,
+                })
+                .collect(),
+            party_size: None,
+            party_max: None,
+            audio_lang,
+            sub_lang,
+            playback_method: None,
+            device: None,
+            extra_buttons: Vec::new(),
+            is_live,
+            channel,
+            is_group: false,
+            group_name: None,
+            next_title: None,
+            composer,
+            work,
         })
     }
+
+    fn selected_stream_language(streams: &[StreamTag], stream_type: i32) -> Option<String> {
+        streams
+            .iter()
+            .find(|s| s.stream_type == stream_type && s.selected)
+            .and_then(|s| s.language.clone())
+    }
+
+    // Plex reports the exact offset landed on after an intro/credits skip a
+    // moment late, so a stray stale update can still show the tail end of
+    // the marker range for a frame. Snap to the marker's boundary instead.
+    fn snap_to_marker_boundary(
+        markers: &[Marker],
+        previous_offset: u64,
+        reported_offset: u64,
+    ) -> u64 {
+        for marker in markers {
+            if marker.marker_type != "intro" && marker.marker_type != "credits" {
+                continue;
+            }
+            let jumped_out_of_marker =
+                previous_offset >= marker.start_ms && previous_offset <= marker.end_ms;
+            let landed_just_past_marker_end = reported_offset >= marker.end_ms
+                && reported_offset - marker.end_ms <= MARKER_SNAP_TOLERANCE_MS;
+            if jumped_out_of_marker && landed_just_past_marker_end {
+                return marker.end_ms;
+            }
+        }
+        reported_offset
+    }
 }
 
 #[derive(Deserialize)]
@@ -372,6 +1284,80 @@ struct PlaySessionState {
     view_offset: Option<u64>,
 }
 
+// The classification `handle_message` derives from a raw SSE payload, split
+// out as a pure function so odd client payloads can be fed in as fixtures
+// without a live server.
+#[derive(Debug, PartialEq)]
+enum SseOutcome {
+    Stopped,
+    Update {
+        rating_key: String,
+        state: PlaybackState,
+        offset: u64,
+    },
+    // Malformed JSON, a notification type we don't care about, or a state
+    // we don't recognize.
+    Ignored,
+}
+
+// Builds the handshake request for Plex's websocket notification endpoint,
+// mirroring the headers `try_connection_sse` sends via `es::ClientBuilder`.
+// `uri` is an `http(s)://` connection address, same as the SSE path; it's
+// rewritten to `ws(s)://` here since that's what the websocket handshake
+// requires.
+fn websocket_request(
+    uri: &str,
+    access_token: &str,
+    client_identifier: &str,
+) -> Result<tokio_tungstenite::tungstenite::http::Request<()>, tokio_tungstenite::tungstenite::Error>
+{
+    let ws_uri = uri
+        .replacen("https://", "wss://", 1)
+        .replacen("http://", "ws://", 1);
+    let url = format!("{ws_uri}/:/websockets/notifications");
+    let mut request = url.into_client_request()?;
+    let headers = request.headers_mut();
+    headers.insert("X-Plex-Token", access_token.parse()?);
+    headers.insert("X-Plex-Client-Identifier", client_identifier.parse()?);
+    Ok(request)
+}
+
+// Whether a websocket handshake failure was Plex rejecting the token (401),
+// as opposed to a network error or some other handshake problem.
+fn is_unauthorized(e: &tokio_tungstenite::tungstenite::Error) -> bool {
+    matches!(
+        e,
+        tokio_tungstenite::tungstenite::Error::Http(resp)
+            if resp.status() == reqwest::StatusCode::UNAUTHORIZED
+    )
+}
+
+fn parse_sse_notification(data: &str) -> SseOutcome {
+    let Ok(notif) = serde_json::from_str::<SseNotification>(data) else {
+        return SseOutcome::Ignored;
+    };
+    let Some(playing) = notif.play_session_state else {
+        return SseOutcome::Ignored;
+    };
+
+    if playing.state == "stopped" {
+        return SseOutcome::Stopped;
+    }
+
+    let state = match playing.state.as_str() {
+        "playing" => PlaybackState::Playing,
+        "paused" => PlaybackState::Paused,
+        "buffering" => PlaybackState::Buffering,
+        _ => return SseOutcome::Ignored,
+    };
+
+    SseOutcome::Update {
+        rating_key: playing.rating_key,
+        state,
+        offset: playing.view_offset.unwrap_or(0),
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "PascalCase")]
 struct SessionsResponse {
@@ -391,6 +1377,29 @@ struct SessionMetadata {
     user: Option<UserInfo>,
     #[serde(rename = "ratingKey")]
     rating_key: Option<String>,
+    #[serde(rename = "viewOffset")]
+    view_offset: Option<u64>,
+    #[serde(rename = "Player")]
+    player: Option<PlayerInfo>,
+    // Present only while Plex is actively transcoding this session.
+    #[serde(rename = "TranscodeSession")]
+    transcode_session: Option<TranscodeSessionTag>,
+    #[serde(rename = "Media", default)]
+    media: Vec<SessionMediaTag>,
+}
+
+#[derive(Deserialize)]
+struct TranscodeSessionTag {}
+
+#[derive(Deserialize)]
+struct SessionMediaTag {
+    #[serde(rename = "Part", default)]
+    parts: Vec<SessionPartTag>,
+}
+
+#[derive(Deserialize)]
+struct SessionPartTag {
+    decision: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -398,6 +1407,15 @@ struct UserInfo {
     title: String,
 }
 
+#[derive(Deserialize)]
+struct PlayerInfo {
+    state: String,
+    // The playing device/app's name, e.g. "Plex for Apple TV". `platform` is
+    // a fallback for older clients that don't report `product`.
+    product: Option<String>,
+    platform: Option<String>,
+}
+
 #[derive(Deserialize)]
 struct GuidTag {
     id: String,
@@ -427,19 +1445,85 @@ struct ItemMetadata {
     title: String,
     #[serde(rename = "type")]
     media_type: String,
+    guid: Option<String>,
     duration: Option<u64>,
+    #[serde(rename = "originallyAvailableAt")]
+    originally_available_at: Option<String>,
     year: Option<u32>,
+    #[serde(rename = "contentRating")]
+    content_rating: Option<String>,
     grandparent_title: Option<String>,
     parent_index: Option<u32>,
     index: Option<u32>,
     parent_title: Option<String>,
+    #[serde(rename = "leafCount")]
+    leaf_count: Option<u32>,
     #[serde(rename = "Guid", default)]
     guids: Vec<GuidTag>,
     #[serde(rename = "Genre", default)]
     genres: Vec<GenreTag>,
+    #[serde(rename = "Director", default)]
+    directors: Vec<GenreTag>,
+    studio: Option<String>,
+    rating: Option<f32>,
+    #[serde(rename = "audienceRating")]
+    audience_rating: Option<f32>,
+    #[serde(rename = "extraType")]
+    extra_type: Option<i32>,
     #[serde(rename = "grandparentKey")]
     grandparent_key: Option<String>,
+    #[serde(rename = "parentKey")]
+    parent_key: Option<String>,
     key: Option<String>,
+    #[serde(rename = "Marker", default)]
+    markers: Vec<MarkerTag>,
+    #[serde(rename = "Media", default)]
+    media: Vec<MediaTag>,
+    thumb: Option<String>,
+    // Plex marks Live TV & DVR sessions with `live: 1` in the metadata.
+    live: Option<i32>,
+    // Classical music fields: libraries without a dedicated composer tag
+    // commonly repurpose the album's studio field for the composer's name,
+    // and store the work's title here with `title` holding the movement
+    // name instead (e.g. title "IV. Allegro", originalTitle "Symphony No. 5").
+    original_title: Option<String>,
+    parent_studio: Option<String>,
+    #[serde(rename = "Composer", default)]
+    composers: Vec<GenreTag>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MarkerTag {
+    #[serde(rename = "type")]
+    marker_type: String,
+    start_time_offset: u64,
+    end_time_offset: u64,
+}
+
+#[derive(Deserialize)]
+struct MediaTag {
+    #[serde(rename = "Part", default)]
+    parts: Vec<PartTag>,
+}
+
+#[derive(Deserialize)]
+struct PartTag {
+    #[serde(rename = "Stream", default)]
+    streams: Vec<StreamTag>,
+}
+
+// Plex marks audio/subtitle type 2/3 respectively; video is type 1.
+const STREAM_TYPE_AUDIO: i32 = 2;
+const STREAM_TYPE_SUBTITLE: i32 = 3;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StreamTag {
+    stream_type: i32,
+    #[serde(default)]
+    selected: bool,
+    language: Option<String>,
 }
 
 #[cfg(test)]
@@ -462,6 +1546,26 @@ mod tests {
         assert!(t.is_duplicate("1", PlaybackState::Playing, 20_000));
     }
 
+    #[test]
+    fn tracker_dedupes_natural_progress_after_a_real_elapsed_delay() {
+        let mut t = PlaybackTracker::default();
+        t.set(playing_info("1", 1000), "http://server");
+        std::thread::sleep(Duration::from_millis(50));
+        // Plex reports an offset close to what elapsed wall-clock time would
+        // predict, as happens during ordinary playback between polls.
+        assert!(t.is_duplicate("1", PlaybackState::Playing, 1030));
+    }
+
+    #[test]
+    fn tracker_detects_a_seek_that_happens_during_the_elapsed_delay() {
+        let mut t = PlaybackTracker::default();
+        t.set(playing_info("1", 1000), "http://server");
+        std::thread::sleep(Duration::from_millis(50));
+        // The reported offset is far from what elapsed time would predict,
+        // as happens when the user scrubs the seek bar between polls.
+        assert!(!t.is_duplicate("1", PlaybackState::Playing, 1000 + SEEK_THRESHOLD_MS * 2));
+    }
+
     #[test]
     fn tracker_treats_seek_as_new_update() {
         let mut t = PlaybackTracker::default();
@@ -503,21 +1607,67 @@ mod tests {
         assert!(t.info.is_none());
     }
 
+    #[test]
+    fn tracker_ranks_playing_above_paused_and_buffering() {
+        assert!(PlaybackState::Playing.priority() > PlaybackState::Paused.priority());
+        assert!(PlaybackState::Playing.priority() > PlaybackState::Buffering.priority());
+        assert!(PlaybackState::Buffering.priority() > PlaybackState::Paused.priority());
+    }
+
+    #[test]
+    fn snaps_offset_landing_just_past_a_marker_end_to_its_boundary() {
+        let markers = vec![Marker {
+            marker_type: "intro".to_string(),
+            start_ms: 0,
+            end_ms: 85_000,
+        }];
+        // Plex reports the skip landing a couple seconds past the marker end
+        assert_eq!(
+            PlexServer::snap_to_marker_boundary(&markers, 5_000, 86_500),
+            85_000
+        );
+    }
+
+    #[test]
+    fn does_not_snap_seeks_unrelated_to_a_marker() {
+        let markers = vec![Marker {
+            marker_type: "intro".to_string(),
+            start_ms: 0,
+            end_ms: 85_000,
+        }];
+        // Previous offset wasn't inside the marker, so this is an ordinary seek
+        assert_eq!(
+            PlexServer::snap_to_marker_boundary(&markers, 200_000, 86_500),
+            86_500
+        );
+        // Landed too far past the marker end to be that skip
+        assert_eq!(
+            PlexServer::snap_to_marker_boundary(&markers, 5_000, 95_000),
+            95_000
+        );
+    }
+
     #[test]
     fn parse_metadata_maps_episode_fields() {
         let meta: ItemMetadata = serde_json::from_str(
             r#"{
             "title": "The One Where It Works",
             "type": "episode",
+            "guid": "plex://episode/5d9c08564eefaa001f6373a8",
             "duration": 1320000,
             "year": 1994,
             "grandparentTitle": "Friends",
             "parentIndex": 1,
             "index": 2,
             "parentTitle": "Season 1",
+            "contentRating": "TV-14",
             "Guid": [{"id": "imdb://tt0583459"}, {"id": "tmdb://123"}],
             "Genre": [{"tag": "Comedy"}],
-            "grandparentKey": "/library/metadata/100"
+            "Director": [{"tag": "James Burrows"}],
+            "studio": "NBC",
+            "grandparentKey": "/library/metadata/100",
+            "parentKey": "/library/metadata/101",
+            "Marker": [{"type": "intro", "startTimeOffset": 0, "endTimeOffset": 85000}]
         }"#,
         )
         .unwrap();
@@ -531,13 +1681,98 @@ mod tests {
         assert_eq!(info.imdb_id.as_deref(), Some("tt0583459"));
         assert_eq!(info.tmdb_id.as_deref(), Some("123"));
         assert_eq!(info.genres, vec!["Comedy".to_string()]);
+        assert_eq!(info.rating.as_deref(), Some("TV-14"));
+        assert_eq!(info.directors, vec!["James Burrows".to_string()]);
+        assert_eq!(info.studio.as_deref(), Some("NBC"));
         assert_eq!(info.duration_ms, 1320000);
         assert_eq!(info.view_offset_ms, 5000);
         assert_eq!(info.rating_key.as_deref(), Some("42"));
+        assert_eq!(
+            info.guid.as_deref(),
+            Some("plex://episode/5d9c08564eefaa001f6373a8")
+        );
+        assert_eq!(info.parent_key.as_deref(), Some("/library/metadata/101"));
         assert_eq!(
             info.grandparent_key.as_deref(),
             Some("/library/metadata/100")
         );
+        assert_eq!(info.markers.len(), 1);
+        assert_eq!(info.markers[0].marker_type, "intro");
+        assert_eq!(info.markers[0].end_ms, 85000);
+        assert!(!info.is_live);
+    }
+
+    #[test]
+    fn parse_metadata_detects_a_live_tv_session_by_its_live_flag() {
+        let meta: ItemMetadata = serde_json::from_str(
+            r#"{
+            "title": "Evening News",
+            "type": "episode",
+            "duration": 0,
+            "grandparentTitle": "NBC",
+            "live": 1
+        }"#,
+        )
+        .unwrap();
+
+        let info = PlexServer::parse_metadata(meta, "42", PlaybackState::Playing, 0).unwrap();
+        assert!(info.is_live);
+        assert_eq!(info.channel.as_deref(), Some("NBC"));
+    }
+
+    #[test]
+    fn parse_metadata_detects_a_live_tv_session_without_a_live_flag() {
+        // Some servers don't set `live` at all, but a zero duration
+        // alongside a channel (grandparent) title is just as telling.
+        let meta: ItemMetadata = serde_json::from_str(
+            r#"{
+            "title": "Evening News",
+            "type": "episode",
+            "duration": 0,
+            "grandparentTitle": "NBC"
+        }"#,
+        )
+        .unwrap();
+
+        let info = PlexServer::parse_metadata(meta, "42", PlaybackState::Playing, 0).unwrap();
+        assert!(info.is_live);
+        assert_eq!(info.channel.as_deref(), Some("NBC"));
+    }
+
+    #[test]
+    fn parse_metadata_maps_selected_audio_and_subtitle_language() {
+        let meta: ItemMetadata = serde_json::from_str(
+            r#"{
+            "title": "The One Where It Works",
+            "type": "episode",
+            "Media": [{
+                "Part": [{
+                    "Stream": [
+                        {"streamType": 1, "selected": true},
+                        {"streamType": 2, "selected": false, "language": "English"},
+                        {"streamType": 2, "selected": true, "language": "Japanese"},
+                        {"streamType": 3, "selected": true, "language": "English"}
+                    ]
+                }]
+            }]
+        }"#,
+        )
+        .unwrap();
+
+        let info = PlexServer::parse_metadata(meta, "42", PlaybackState::Playing, 0).unwrap();
+        assert_eq!(info.audio_lang.as_deref(), Some("Japanese"));
+        assert_eq!(info.sub_lang.as_deref(), Some("English"));
+    }
+
+    #[test]
+    fn parse_metadata_leaves_language_unset_when_no_stream_is_selected() {
+        let meta: ItemMetadata =
+            serde_json::from_str(r#"{"title": "The One Where It Works", "type": "episode"}"#)
+                .unwrap();
+
+        let info = PlexServer::parse_metadata(meta, "42", PlaybackState::Playing, 0).unwrap();
+        assert_eq!(info.audio_lang, None);
+        assert_eq!(info.sub_lang, None);
     }
 
     #[test]
@@ -547,7 +1782,9 @@ mod tests {
             "title": "Song",
             "type": "track",
             "grandparentTitle": "Artist",
-            "parentTitle": "Album"
+            "parentTitle": "Album",
+            "index": 3,
+            "leafCount": 12
         }"#,
         )
         .unwrap();
@@ -557,6 +1794,50 @@ mod tests {
         assert_eq!(info.artist.as_deref(), Some("Artist"));
         assert_eq!(info.album.as_deref(), Some("Album"));
         assert_eq!(info.duration_ms, 0);
+        assert_eq!(info.track_number, Some(3));
+        assert_eq!(info.track_total, Some(12));
+    }
+
+    #[test]
+    fn parse_metadata_maps_classical_composer_and_work_fields() {
+        let meta: ItemMetadata = serde_json::from_str(
+            r#"{
+            "title": "IV. Allegro",
+            "type": "track",
+            "originalTitle": "Symphony No. 5",
+            "Composer": [{"tag": "Ludwig van Beethoven"}]
+        }"#,
+        )
+        .unwrap();
+
+        let info = PlexServer::parse_metadata(meta, "8", PlaybackState::Playing, 0).unwrap();
+        assert_eq!(info.composer.as_deref(), Some("Ludwig van Beethoven"));
+        assert_eq!(info.work.as_deref(), Some("Symphony No. 5"));
+    }
+
+    #[test]
+    fn parse_metadata_falls_back_to_parent_studio_for_composer_without_a_tag() {
+        let meta: ItemMetadata = serde_json::from_str(
+            r#"{
+            "title": "IV. Allegro",
+            "type": "track",
+            "parentStudio": "Ludwig van Beethoven"
+        }"#,
+        )
+        .unwrap();
+
+        let info = PlexServer::parse_metadata(meta, "9", PlaybackState::Playing, 0).unwrap();
+        assert_eq!(info.composer.as_deref(), Some("Ludwig van Beethoven"));
+    }
+
+    #[test]
+    fn parse_metadata_maps_clip_type() {
+        let meta: ItemMetadata =
+            serde_json::from_str(r#"{"title": "Music Video", "type": "clip"}"#).unwrap();
+
+        let info = PlexServer::parse_metadata(meta, "9", PlaybackState::Playing, 0).unwrap();
+        assert_eq!(info.media_type, MediaType::Clip);
+        assert_eq!(info.title, "Music Video");
     }
 
     #[test]
@@ -566,6 +1847,19 @@ mod tests {
         assert!(PlexServer::parse_metadata(meta, "1", PlaybackState::Playing, 0).is_none());
     }
 
+    #[test]
+    fn extra_type_deserializes_from_plex_payload() {
+        let meta: ItemMetadata =
+            serde_json::from_str(r#"{"title": "Trailer", "type": "clip", "extraType": 1}"#)
+                .unwrap();
+        assert_eq!(meta.extra_type, Some(1));
+
+        let meta: ItemMetadata =
+            serde_json::from_str(r#"{"title": "The One Where It Works", "type": "episode"}"#)
+                .unwrap();
+        assert_eq!(meta.extra_type, None);
+    }
+
     #[test]
     fn sse_notification_parses_plex_payload() {
         let notif: SseNotification = serde_json::from_str(
@@ -583,4 +1877,342 @@ mod tests {
         assert_eq!(playing.rating_key, "123");
         assert_eq!(playing.view_offset, Some(60000));
     }
+
+    #[test]
+    fn parse_sse_notification_handles_playing_paused_and_buffering() {
+        for (wire_state, state) in [
+            ("playing", PlaybackState::Playing),
+            ("paused", PlaybackState::Paused),
+            ("buffering", PlaybackState::Buffering),
+        ] {
+            let data = format!(
+                r#"{{"PlaySessionStateNotification": {{"state": "{wire_state}", "ratingKey": "123", "viewOffset": 60000}}}}"#
+            );
+            assert_eq!(
+                parse_sse_notification(&data),
+                SseOutcome::Update {
+                    rating_key: "123".to_string(),
+                    state,
+                    offset: 60000,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn parse_sse_notification_defaults_missing_offset_to_zero() {
+        let data = r#"{"PlaySessionStateNotification": {"state": "playing", "ratingKey": "123"}}"#;
+        assert_eq!(
+            parse_sse_notification(data),
+            SseOutcome::Update {
+                rating_key: "123".to_string(),
+                state: PlaybackState::Playing,
+                offset: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_sse_notification_recognizes_stopped() {
+        let data = r#"{"PlaySessionStateNotification": {"state": "stopped", "ratingKey": "123"}}"#;
+        assert_eq!(parse_sse_notification(data), SseOutcome::Stopped);
+    }
+
+    #[test]
+    fn parse_sse_notification_ignores_unknown_states() {
+        let data = r#"{"PlaySessionStateNotification": {"state": "weird", "ratingKey": "123"}}"#;
+        assert_eq!(parse_sse_notification(data), SseOutcome::Ignored);
+    }
+
+    #[test]
+    fn parse_sse_notification_ignores_unrelated_notification_types() {
+        assert_eq!(
+            parse_sse_notification(r#"{"TranscodeSession": {}}"#),
+            SseOutcome::Ignored
+        );
+    }
+
+    #[test]
+    fn parse_sse_notification_ignores_malformed_payloads() {
+        assert_eq!(
+            parse_sse_notification("not json at all"),
+            SseOutcome::Ignored
+        );
+        assert_eq!(parse_sse_notification(""), SseOutcome::Ignored);
+    }
+
+    #[test]
+    fn sessions_response_parses_plex_payload() {
+        let resp: SessionsResponse = serde_json::from_str(
+            r#"{
+            "MediaContainer": {
+                "Metadata": [{
+                    "ratingKey": "123",
+                    "viewOffset": 60000,
+                    "User": {"title": "alice"},
+                    "Player": {"state": "playing"}
+                }]
+            }
+        }"#,
+        )
+        .unwrap();
+        let session = &resp.media_container.metadata[0];
+        assert_eq!(session.rating_key.as_deref(), Some("123"));
+        assert_eq!(session.view_offset, Some(60000));
+        assert_eq!(session.user.as_ref().unwrap().title, "alice");
+        assert_eq!(session.player.as_ref().unwrap().state, "playing");
+    }
+
+    fn session_stub(rating_key: &str) -> SessionMetadata {
+        serde_json::from_str(&format!(r#"{{"ratingKey": "{rating_key}"}}"#)).unwrap()
+    }
+
+    #[test]
+    fn count_watching_ignores_solo_sessions() {
+        let sessions = vec![session_stub("1"), session_stub("2")];
+        assert_eq!(PlexServer::count_watching(&sessions, "1"), None);
+    }
+
+    #[test]
+    fn count_watching_counts_sessions_sharing_an_item() {
+        let sessions = vec![session_stub("1"), session_stub("1"), session_stub("2")];
+        assert_eq!(PlexServer::count_watching(&sessions, "1"), Some(2));
+    }
+
+    fn session_stub_with_user(rating_key: &str, username: &str) -> SessionMetadata {
+        serde_json::from_str(&format!(
+            r#"{{"ratingKey": "{rating_key}", "User": {{"title": "{username}"}}}}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn resolve_group_viewers_is_none_for_a_solo_session() {
+        let sessions = vec![session_stub_with_user("1", "alice")];
+        assert_eq!(
+            PlexServer::resolve_group_viewers(&sessions, "1", None),
+            None
+        );
+    }
+
+    #[test]
+    fn resolve_group_viewers_lists_the_others_excluding_the_owner() {
+        let sessions = vec![
+            session_stub_with_user("1", "alice"),
+            session_stub_with_user("1", "bob"),
+            session_stub_with_user("1", "carol"),
+            session_stub_with_user("2", "dave"),
+        ];
+        assert_eq!(
+            PlexServer::resolve_group_viewers(&sessions, "1", Some("alice")),
+            Some(vec!["bob".to_string(), "carol".to_string()])
+        );
+    }
+
+    #[test]
+    fn resolve_group_viewers_includes_everyone_when_the_owner_is_unknown() {
+        let sessions = vec![
+            session_stub_with_user("1", "alice"),
+            session_stub_with_user("1", "bob"),
+        ];
+        assert_eq!(
+            PlexServer::resolve_group_viewers(&sessions, "1", None),
+            Some(vec!["alice".to_string(), "bob".to_string()])
+        );
+    }
+
+    #[test]
+    fn select_session_matches_the_username() {
+        let sessions = vec![
+            session_stub_with_user("1", "alice"),
+            session_stub_with_user("2", "bob"),
+        ];
+        let session = PlexServer::select_session(sessions, Some("bob"), false).unwrap();
+        assert_eq!(session.rating_key.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn select_session_is_none_when_no_username_matches_and_fallback_is_disabled() {
+        let sessions = vec![session_stub_with_user("1", "alice")];
+        assert!(PlexServer::select_session(sessions, Some("bob"), false).is_none());
+    }
+
+    #[test]
+    fn select_session_falls_back_to_the_sole_session_when_no_username_matches() {
+        let sessions = vec![session_stub("1")];
+        let session = PlexServer::select_session(sessions, Some("bob"), true).unwrap();
+        assert_eq!(session.rating_key.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn select_session_does_not_fall_back_when_more_than_one_session_is_active() {
+        let sessions = vec![session_stub("1"), session_stub("2")];
+        assert!(PlexServer::select_session(sessions, Some("bob"), true).is_none());
+    }
+
+    #[test]
+    fn select_session_without_a_username_returns_the_first_session() {
+        let sessions = vec![session_stub("1"), session_stub("2")];
+        let session = PlexServer::select_session(sessions, None, false).unwrap();
+        assert_eq!(session.rating_key.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn resolve_playback_method_detects_transcode_session() {
+        let sessions: Vec<SessionMetadata> = serde_json::from_str(
+            r#"[{"ratingKey": "1", "TranscodeSession": {"videoDecision": "transcode"}}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            PlexServer::resolve_playback_method(&sessions, "1"),
+            Some("Transcode")
+        );
+    }
+
+    #[test]
+    fn resolve_playback_method_reads_part_decision_without_a_transcode_session() {
+        let sessions: Vec<SessionMetadata> = serde_json::from_str(
+            r#"[{"ratingKey": "1", "Media": [{"Part": [{"decision": "directplay"}]}]}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            PlexServer::resolve_playback_method(&sessions, "1"),
+            Some("Direct Play")
+        );
+    }
+
+    #[test]
+    fn resolve_playback_method_is_none_for_unknown_session() {
+        let sessions = vec![session_stub("1")];
+        assert_eq!(PlexServer::resolve_playback_method(&sessions, "2"), None);
+    }
+
+    #[test]
+    fn resolve_device_prefers_product_over_platform() {
+        let sessions: Vec<SessionMetadata> = serde_json::from_str(
+            r#"[{"ratingKey": "1", "Player": {"state": "playing", "product": "Plex for Apple TV", "platform": "tvOS"}}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            PlexServer::resolve_device(&sessions, "1"),
+            Some("Plex for Apple TV".to_string())
+        );
+    }
+
+    #[test]
+    fn resolve_device_falls_back_to_platform_without_a_product() {
+        let sessions: Vec<SessionMetadata> = serde_json::from_str(
+            r#"[{"ratingKey": "1", "Player": {"state": "playing", "platform": "Android"}}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            PlexServer::resolve_device(&sessions, "1"),
+            Some("Android".to_string())
+        );
+    }
+
+    #[test]
+    fn resolve_device_is_none_for_unknown_session() {
+        let sessions = vec![session_stub("1")];
+        assert_eq!(PlexServer::resolve_device(&sessions, "2"), None);
+    }
+
+    #[test]
+    fn plex_artwork_url_builds_a_transcode_request_with_the_token() {
+        let server = PlexServer::new(
+            "Living Room".to_string(),
+            Vec::new(),
+            "secret-token".to_string(),
+            None,
+            PlexServerOptions {
+                ignore_extras: false,
+                allow_insecure_tls: false,
+                notification_transport: NotificationTransport::Sse,
+                poll_fallback_interval_secs: 0,
+                stale_session_check_interval_secs: 0,
+                use_plex_artwork: true,
+                art_proxy_public_base_url: None,
+                fallback_to_any_session_when_no_user: false,
+                http_timeout_secs: 10,
+                sse_connect_timeout_secs: 15,
+                client_identifier: "presence-for-plex".to_string(),
+                user_agent: "PresenceForPlex/1.0".to_string(),
+            },
+        );
+        let url =
+            server.plex_artwork_url("https://plex.example.com", "/library/metadata/1/thumb/123");
+        assert!(url.starts_with("https://plex.example.com/photo/:/transcode?"));
+        assert!(url.contains("X-Plex-Token=secret-token"));
+        assert!(url.contains(
+            "url=https%3A%2F%2Fplex%2Eexample%2Ecom%2Flibrary%2Fmetadata%2F1%2Fthumb%2F123"
+        ));
+    }
+
+    #[test]
+    fn websocket_request_rewrites_the_scheme_and_sets_the_plex_headers() {
+        let request = websocket_request(
+            "https://plex.example.com:32400",
+            "secret-token",
+            crate::plex_account::APP_NAME,
+        )
+        .unwrap();
+        assert_eq!(
+            request.uri().to_string(),
+            "wss://plex.example.com:32400/:/websockets/notifications"
+        );
+        assert_eq!(request.headers()["X-Plex-Token"], "secret-token");
+        assert_eq!(
+            request.headers()["X-Plex-Client-Identifier"],
+            crate::plex_account::APP_NAME
+        );
+    }
+
+    #[test]
+    fn websocket_request_rewrites_plain_http_too() {
+        let request = websocket_request(
+            "http://192.168.1.5:32400",
+            "secret-token",
+            crate::plex_account::APP_NAME,
+        )
+        .unwrap();
+        assert_eq!(
+            request.uri().to_string(),
+            "ws://192.168.1.5:32400/:/websockets/notifications"
+        );
+    }
+
+    #[test]
+    fn websocket_request_rejects_a_token_that_is_not_a_valid_header_value_instead_of_panicking() {
+        let result = websocket_request(
+            "https://plex.example.com:32400",
+            "bad\ntoken",
+            crate::plex_account::APP_NAME,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn is_unauthorized_detects_a_401_handshake_response() {
+        let resp: tokio_tungstenite::tungstenite::http::Response<Option<Vec<u8>>> =
+            tokio_tungstenite::tungstenite::http::Response::builder()
+                .status(reqwest::StatusCode::UNAUTHORIZED)
+                .body(None)
+                .unwrap();
+        let err = tokio_tungstenite::tungstenite::Error::Http(Box::new(resp));
+        assert!(is_unauthorized(&err));
+    }
+
+    #[test]
+    fn is_unauthorized_is_false_for_other_handshake_failures() {
+        let resp: tokio_tungstenite::tungstenite::http::Response<Option<Vec<u8>>> =
+            tokio_tungstenite::tungstenite::http::Response::builder()
+                .status(reqwest::StatusCode::NOT_FOUND)
+                .body(None)
+                .unwrap();
+        let err = tokio_tungstenite::tungstenite::Error::Http(Box::new(resp));
+        assert!(!is_unauthorized(&err));
+        assert!(!is_unauthorized(
+            &tokio_tungstenite::tungstenite::Error::ConnectionClosed
+        ));
+    }
 }
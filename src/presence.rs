@@ -1,11 +1,63 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
 use crate::config::Config;
-use crate::discord::{ActivityType, Button, Presence};
-use crate::media::{MediaInfo, MediaType};
+use crate::discord::{ActivityType, Button, Presence, TimestampMode};
+use crate::media::{MediaInfo, MediaType, PlaybackState};
 
 const DEFAULT_IMAGE: &str = "plex_logo";
 
+// Discord activities support at most two buttons.
+pub(crate) const MAX_BUTTONS: usize = 2;
+
+// Which external-service link to show as a Discord button, and in what
+// order `Config::button_sources` lists them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ButtonSource {
+    Imdb,
+    Mal,
+    Tmdb,
+    // Trakt doesn't get its own id tracked; its IMDb search redirects
+    // straight to the matching title page, so this reuses `imdb_id`.
+    Trakt,
+    // Manual overrides from `overrides.yaml`, via `MediaInfo::extra_buttons`.
+    Custom,
+}
+
 pub fn build_presence(info: &MediaInfo, config: &Config) -> Presence {
+    let display_info: Cow<MediaInfo> =
+        if config.clean_movie_titles && info.media_type == MediaType::Movie {
+            let mut cleaned = info.clone();
+            cleaned.title = clean_movie_title(&info.title);
+            Cow::Owned(cleaned)
+        } else if config.episode_title_fallback
+            && info.media_type == MediaType::Episode
+            && info.title.trim().is_empty()
+        {
+            let mut fallback = info.clone();
+            fallback.title = episode_title_fallback(info.episode);
+            Cow::Owned(fallback)
+        } else {
+            Cow::Borrowed(info)
+        };
+    let info = display_info.as_ref();
+
+    let is_anime_episode = info.media_type == MediaType::Episode
+        && (info.mal_id.is_some() || info.is_anime(&config.anime_genre_keywords));
+
     let (details_tpl, state_tpl, image_tpl) = match info.media_type {
+        MediaType::Episode if is_anime_episode => (
+            config.anime_details.as_ref().unwrap_or(&config.tv_details),
+            config.anime_state.as_ref().unwrap_or(&config.tv_state),
+            config
+                .anime_image_text
+                .as_ref()
+                .unwrap_or(&config.tv_image_text),
+        ),
         MediaType::Episode => (&config.tv_details, &config.tv_state, &config.tv_image_text),
         MediaType::Movie => (
             &config.movie_details,
@@ -17,49 +69,404 @@ pub fn build_presence(info: &MediaInfo, config: &Config) -> Presence {
             &config.music_state,
             &config.music_image_text,
         ),
+        MediaType::Clip => (
+            &config.clip_details,
+            &config.clip_state,
+            &config.clip_image_text,
+        ),
     };
 
-    let mut buttons = Vec::new();
-    if config.show_buttons {
-        if let Some(ref id) = info.mal_id {
-            buttons.push(Button {
-                label: "View on MyAnimeList".into(),
-                url: format!("https://myanimelist.net/anime/{}", id),
-            });
-        }
-        if let Some(ref id) = info.imdb_id
-            && buttons.len() < 2
-        {
-            buttons.push(Button {
-                label: "View on IMDb".into(),
-                url: format!("https://www.imdb.com/title/{}", id),
-            });
-        }
-    }
+    // Spotify-style "Listening to {artist}" moves the artist onto the details
+    // line, which Discord renders as the activity's leading text for Listening.
+    let (details_tpl, state_tpl) =
+        if info.media_type == MediaType::Track && config.music_artist_forward {
+            (state_tpl, details_tpl)
+        } else {
+            (details_tpl, state_tpl)
+        };
+
+    let buttons = build_buttons(info, config);
+
+    let details = if config.watching_title_preset {
+        let preset_tpl = if info.media_type == MediaType::Episode {
+            "{show}"
+        } else {
+            "{title}"
+        };
+        format_template(preset_tpl, info, config.air_date_format)
+    } else {
+        format_template(details_tpl, info, config.air_date_format)
+    };
 
     Presence {
-        details: format_template(details_tpl, info),
-        state: format_template(state_tpl, info),
-        large_image: Some(if config.show_artwork {
-            info.art_url.clone().unwrap_or_else(|| DEFAULT_IMAGE.into())
+        details: guard_empty_field(
+            truncate_field(details, config.template_max_len),
+            "details",
+            &config.empty_field_placeholder,
+        ),
+        state: guard_empty_field(
+            truncate_field(
+                format_template(state_tpl, info, config.air_date_format),
+                config.template_max_len,
+            ),
+            "state",
+            &config.empty_field_placeholder,
+        ),
+        large_image: Some(if show_artwork(info.media_type, config) {
+            info.art_url
+                .clone()
+                .or_else(|| offline_artwork_url(info, config))
+                .unwrap_or_else(|| DEFAULT_IMAGE.into())
         } else {
             DEFAULT_IMAGE.into()
         }),
-        large_image_text: format_template(image_tpl, info),
+        large_image_text: truncate_field(
+            format_template(image_tpl, info, config.air_date_format),
+            config.template_max_len,
+        ),
+        small_image_key: small_image_for_genres(&info.genres, &config.genre_small_images)
+            .map(String::from),
         progress_ms: info.view_offset_ms,
         duration_ms: info.duration_ms,
-        show_timestamps: config.show_progress,
-        activity_type: if info.media_type == MediaType::Track {
-            ActivityType::Listening
+        // Live sessions don't have a meaningful duration, so a start/end
+        // timestamp pair would render as nonsense (e.g. an "ends in 9999
+        // hours" countdown) rather than anything useful.
+        timestamp_mode: if info.is_live {
+            TimestampMode::None
+        } else if !config.freeze_paused_timestamp
+            && matches!(info.state, PlaybackState::Paused | PlaybackState::Buffering)
+        {
+            // The alternative to DiscordClient's far-future-offset freeze
+            // hack: just omit timestamps entirely while paused/buffering.
+            TimestampMode::None
         } else {
+            config.timestamp_mode
+        },
+        activity_type: if config.watching_title_preset {
             ActivityType::Watching
+        } else if info.is_group && config.group_activity_type.is_some() {
+            config.group_activity_type.unwrap()
+        } else {
+            activity_type_override(info.media_type, config).unwrap_or(
+                if info.media_type == MediaType::Track {
+                    ActivityType::Listening
+                } else {
+                    ActivityType::Watching
+                },
+            )
         },
         playback_state: info.state,
         buttons,
+        party_size: info.party_size,
+        party_max: info.party_max,
+    }
+}
+
+// A static activity shown while nothing is playing, for users who'd rather
+// see "Browsing Plex" than have presence disappear entirely.
+pub fn build_idle_presence(config: &Config) -> Presence {
+    Presence {
+        details: config.idle_details.clone(),
+        state: config.idle_state.clone(),
+        large_image: Some(DEFAULT_IMAGE.into()),
+        large_image_text: String::new(),
+        small_image_key: None,
+        progress_ms: 0,
+        duration_ms: 0,
+        timestamp_mode: TimestampMode::None,
+        activity_type: ActivityType::Watching,
+        playback_state: PlaybackState::Playing,
+        buttons: Vec::new(),
+        party_size: None,
+        party_max: None,
+    }
+}
+
+// Strips a trailing parenthetical suffix from a movie title, e.g. an
+// edition tag ("Blade Runner (Director's Cut)") or a disambiguating year
+// ("Dune (2021)") that Plex sometimes bakes into the title itself.
+fn clean_movie_title(title: &str) -> String {
+    let trimmed = title.trim_end();
+    match trimmed.ends_with(')').then(|| trimmed.rfind('(')).flatten() {
+        Some(open) => trimmed[..open].trim_end().to_string(),
+        None => trimmed.to_string(),
+    }
+}
+
+// Stand-in title for a blank episode title, used when
+// `Config::episode_title_fallback` is on.
+fn episode_title_fallback(episode: Option<u32>) -> String {
+    match episode {
+        Some(n) => format!("Episode {n}"),
+        None => "Episode".to_string(),
+    }
+}
+
+// Per-media-type override of `show_artwork`, falling back to the global
+// flag when the specific type hasn't been configured.
+fn show_artwork(media_type: MediaType, config: &Config) -> bool {
+    let override_flag = match media_type {
+        MediaType::Movie => config.show_artwork_movies,
+        MediaType::Episode => config.show_artwork_tv,
+        MediaType::Track => config.show_artwork_music,
+        MediaType::Clip => None,
+    };
+    override_flag.unwrap_or(config.show_artwork)
+}
+
+// Builds Discord buttons from `config.button_sources`, in the configured
+// order, skipping any source whose id isn't available and stopping once
+// `MAX_BUTTONS` is reached (Discord doesn't render more than two).
+fn build_buttons(info: &MediaInfo, config: &Config) -> Vec<Button> {
+    if !config.show_buttons {
+        return Vec::new();
+    }
+    let mut buttons = Vec::new();
+    for source in &config.button_sources {
+        if buttons.len() >= MAX_BUTTONS {
+            break;
+        }
+        match source {
+            ButtonSource::Mal => {
+                if let Some(ref id) = info.mal_id {
+                    buttons.push(Button {
+                        label: "View on MyAnimeList".into(),
+                        url: format!("https://myanimelist.net/anime/{}", id),
+                    });
+                }
+            }
+            ButtonSource::Imdb => {
+                if let Some(ref id) = info.imdb_id {
+                    buttons.push(Button {
+                        label: "View on IMDb".into(),
+                        url: format!("https://www.imdb.com/title/{}", id),
+                    });
+                }
+            }
+            ButtonSource::Tmdb => {
+                if let Some(ref id) = info.tmdb_id {
+                    let kind = if info.media_type == MediaType::Movie {
+                        "movie"
+                    } else {
+                        "tv"
+                    };
+                    buttons.push(Button {
+                        label: "View on TMDB".into(),
+                        url: format!("https://www.themoviedb.org/{kind}/{id}"),
+                    });
+                }
+            }
+            ButtonSource::Trakt => {
+                if let Some(ref id) = info.imdb_id {
+                    buttons.push(Button {
+                        label: "View on Trakt".into(),
+                        url: format!("https://trakt.tv/search/imdb/{id}"),
+                    });
+                }
+            }
+            ButtonSource::Custom => {
+                let remaining = MAX_BUTTONS.saturating_sub(buttons.len());
+                buttons.extend(info.extra_buttons.iter().take(remaining).cloned());
+            }
+        }
+    }
+    buttons
+}
+
+fn activity_type_override(media_type: MediaType, config: &Config) -> Option<ActivityType> {
+    match media_type {
+        MediaType::Movie => config.movie_activity_type,
+        MediaType::Episode => config.tv_activity_type,
+        MediaType::Track => config.music_activity_type,
+        MediaType::Clip => config.clip_activity_type,
+    }
+}
+
+// The first of the item's genres (in its own order) that has a custom small
+// image asset key configured, matched case-insensitively so "Horror" in
+// config matches a genre reported as "horror".
+fn small_image_for_genres<'a>(
+    genres: &[String],
+    map: &'a HashMap<String, String>,
+) -> Option<&'a str> {
+    genres.iter().find_map(|g| {
+        map.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(g))
+            .map(|(_, v)| v.as_str())
+    })
+}
+
+// Falls back to a locally bundled artwork image, served through the art
+// proxy, when no art_url was resolved (e.g. every external provider is
+// down). None unless both `offline_artwork_dir` and
+// `art_proxy_public_base_url` are configured, since the image still needs
+// a URL Discord can fetch.
+fn offline_artwork_url(info: &MediaInfo, config: &Config) -> Option<String> {
+    config.offline_artwork_dir.as_ref()?;
+    let base = config.art_proxy_public_base_url.as_ref()?;
+    let filename = small_image_for_genres(&info.genres, &config.genre_offline_artwork)
+        .map(String::from)
+        .unwrap_or_else(|| offline_artwork_filename(info.media_type).to_string());
+    Some(crate::art_proxy::offline_url(base, &filename))
+}
+
+// The media-type-keyed filename `offline_artwork_dir` is expected to
+// contain, absent a more specific genre match.
+fn offline_artwork_filename(media_type: MediaType) -> &'static str {
+    match media_type {
+        MediaType::Movie => "movie.png",
+        MediaType::Episode => "episode.png",
+        MediaType::Track => "track.png",
+        MediaType::Clip => "clip.png",
+    }
+}
+
+const KNOWN_PLACEHOLDERS: &[&str] = &[
+    "show",
+    "title",
+    "se",
+    "season",
+    "episode",
+    "episode_total",
+    "next_title",
+    "year",
+    "rating",
+    "director",
+    "studio",
+    "network",
+    "score",
+    "genres",
+    "artist",
+    "album",
+    "composer",
+    "work",
+    "track",
+    "track_total",
+    "audio_lang",
+    "sub_lang",
+    "playback_method",
+    "device",
+    "progress",
+    "remaining",
+    "percent",
+    "air_date",
+    "channel",
+    "server",
+    "is_group",
+    "group_name",
+];
+
+// How `{air_date}` renders `MediaInfo::original_air_date`, which Plex
+// reports as a plain "YYYY-MM-DD" string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AirDateFormat {
+    Iso,
+    Localized,
+}
+
+const MONTH_ABBREVIATIONS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// Plex's `originallyAvailableAt` is already "YYYY-MM-DD"; `Localized`
+// reformats it as e.g. "Apr 14, 2019". Falls back to the raw string if it
+// doesn't parse, rather than dropping it entirely.
+fn format_air_date(raw: &str, format: AirDateFormat) -> String {
+    if format == AirDateFormat::Iso {
+        return raw.to_string();
+    }
+    let parts: Vec<&str> = raw.split('-').collect();
+    let [year, month, day] = parts[..] else {
+        return raw.to_string();
+    };
+    let Ok(month_num) = month.parse::<usize>() else {
+        return raw.to_string();
+    };
+    let Some(month_name) = month_num
+        .checked_sub(1)
+        .and_then(|i| MONTH_ABBREVIATIONS.get(i))
+    else {
+        return raw.to_string();
+    };
+    let day = day.trim_start_matches('0');
+    format!("{month_name} {day}, {year}")
+}
+
+// Placeholder names referenced by `template` that `format_template` doesn't
+// recognize, so callers can warn about likely typos (e.g. `{titel}`)
+// instead of silently emitting them verbatim.
+pub fn unknown_placeholders(template: &str) -> Vec<String> {
+    let mut chars = template.chars().peekable();
+    let mut unknown = Vec::new();
+    while let Some(c) = chars.next() {
+        if c == '{' {
+            if chars.peek() == Some(&'{') {
+                chars.next();
+                continue;
+            }
+            let mut placeholder = String::new();
+            let mut closed = false;
+            for ch in chars.by_ref() {
+                if ch == '}' {
+                    closed = true;
+                    break;
+                }
+                placeholder.push(ch);
+            }
+            let base = placeholder.split(':').next().unwrap_or(&placeholder);
+            if closed && !KNOWN_PLACEHOLDERS.contains(&base) {
+                unknown.push(placeholder);
+            }
+        } else if c == '}' && chars.peek() == Some(&'}') {
+            chars.next();
+        }
+    }
+    unknown
+}
+
+// Caps a rendered template field at `max_len` graphemes, if configured,
+// appending an ellipsis when anything is cut. Separate from Discord's own
+// hard field cap (`discord::truncate_for_discord`) — this lets templates
+// control overflow themselves, e.g. trimming a long `{genres}` list down
+// before Discord would otherwise cut it off mid-word.
+fn truncate_field(text: String, max_len: Option<usize>) -> String {
+    let Some(max_len) = max_len else {
+        return text;
+    };
+    if text.graphemes(true).count() <= max_len {
+        return text;
+    }
+    let mut truncated: String = text
+        .graphemes(true)
+        .take(max_len.saturating_sub(1))
+        .collect();
+    truncated.push('…');
+    truncated
+}
+
+// Discord rejects an activity whose `details`/`state` is empty or
+// whitespace-only. Substitutes `placeholder` (logging that it did) when a
+// template rendered nothing, leaving it blank untouched when unconfigured
+// so the existing drop-if-blank behavior in `DiscordClient::update` applies.
+fn guard_empty_field(value: String, field_name: &str, placeholder: &Option<String>) -> String {
+    if !value.trim().is_empty() {
+        return value;
+    }
+    match placeholder {
+        Some(replacement) => {
+            warn!("{field_name} template rendered empty; using configured placeholder");
+            replacement.clone()
+        }
+        None => value,
     }
 }
 
-fn format_template(template: &str, info: &MediaInfo) -> String {
+pub(crate) fn format_mm_ss(ms: u64) -> String {
+    let total_secs = ms / 1000;
+    format!("{}:{:02}", total_secs / 60, total_secs % 60)
+}
+
+fn format_template(template: &str, info: &MediaInfo, air_date_format: AirDateFormat) -> String {
     let mut result = String::with_capacity(template.len() + 32);
     let mut chars = template.chars().peekable();
 
@@ -85,7 +492,11 @@ fn format_template(template: &str, info: &MediaInfo) -> String {
                 result.push_str(&placeholder);
                 break;
             }
-            match placeholder.as_str() {
+            let (name, arg) = match placeholder.split_once(':') {
+                Some((name, arg)) => (name, Some(arg)),
+                None => (placeholder.as_str(), None),
+            };
+            match name {
                 "show" => result.push_str(info.show_name.as_deref().unwrap_or("")),
                 "title" => result.push_str(&info.title),
                 "se" => {
@@ -103,14 +514,68 @@ fn format_template(template: &str, info: &MediaInfo) -> String {
                         result.push_str(&e.to_string());
                     }
                 }
+                "episode_total" => {
+                    if let Some(t) = info.episode_total {
+                        result.push_str(&t.to_string());
+                    }
+                }
+                "next_title" => result.push_str(info.next_title.as_deref().unwrap_or("")),
                 "year" => {
                     if let Some(y) = info.year {
                         result.push_str(&y.to_string());
                     }
                 }
-                "genres" => result.push_str(&info.genres.join(", ")),
+                "rating" => result.push_str(info.rating.as_deref().unwrap_or("")),
+                "director" => result.push_str(&info.directors.join(", ")),
+                "studio" => result.push_str(info.studio.as_deref().unwrap_or("")),
+                "network" => result.push_str(info.network.as_deref().unwrap_or("")),
+                "score" => {
+                    if let Some(s) = info.audience_rating.or(info.critic_rating) {
+                        result.push_str(&format!("{s:.1}"));
+                    }
+                }
+                "genres" => match arg.and_then(|n| n.parse::<usize>().ok()) {
+                    Some(n) => result.push_str(&info.genres[..info.genres.len().min(n)].join(", ")),
+                    None => result.push_str(&info.genres.join(", ")),
+                },
                 "artist" => result.push_str(info.artist.as_deref().unwrap_or("")),
                 "album" => result.push_str(info.album.as_deref().unwrap_or("")),
+                "composer" => result.push_str(info.composer.as_deref().unwrap_or("")),
+                "work" => result.push_str(info.work.as_deref().unwrap_or("")),
+                "track" => {
+                    if let Some(t) = info.track_number {
+                        result.push_str(&t.to_string());
+                    }
+                }
+                "track_total" => {
+                    if let Some(t) = info.track_total {
+                        result.push_str(&t.to_string());
+                    }
+                }
+                "audio_lang" => result.push_str(info.audio_lang.as_deref().unwrap_or("")),
+                "sub_lang" => result.push_str(info.sub_lang.as_deref().unwrap_or("")),
+                "playback_method" => result.push_str(info.playback_method.as_deref().unwrap_or("")),
+                "device" => result.push_str(info.device.as_deref().unwrap_or("")),
+                "air_date" => {
+                    if let Some(ref date) = info.original_air_date {
+                        result.push_str(&format_air_date(date, air_date_format));
+                    }
+                }
+                "channel" => result.push_str(info.channel.as_deref().unwrap_or("")),
+                "server" => result.push_str(&info.server),
+                "is_group" => result.push_str(if info.is_group { "Group" } else { "" }),
+                "group_name" => result.push_str(info.group_name.as_deref().unwrap_or("")),
+                "progress" => result.push_str(&format_mm_ss(info.view_offset_ms)),
+                "remaining" => result.push_str(&format_mm_ss(
+                    info.duration_ms.saturating_sub(info.view_offset_ms),
+                )),
+                "percent" => {
+                    if info.duration_ms > 0 {
+                        let percent = (info.view_offset_ms as f64 / info.duration_ms as f64 * 100.0)
+                            .round() as u64;
+                        result.push_str(&percent.to_string());
+                    }
+                }
                 _ => {
                     result.push('{');
                     result.push_str(&placeholder);
@@ -145,41 +610,77 @@ mod tests {
     #[test]
     fn replaces_known_placeholders() {
         let info = episode_info();
-        assert_eq!(format_template("{show}: {title}", &info), "The Show: Pilot");
-        assert_eq!(format_template("S{season} · E{episode}", &info), "S1 · E2");
         assert_eq!(
-            format_template("{year} [{genres}]", &info),
+            format_template("{show}: {title}", &info, AirDateFormat::Iso),
+            "The Show: Pilot"
+        );
+        assert_eq!(
+            format_template("S{season} · E{episode}", &info, AirDateFormat::Iso),
+            "S1 · E2"
+        );
+        assert_eq!(
+            format_template("{year} [{genres}]", &info, AirDateFormat::Iso),
             "2020 [Drama, Comedy]"
         );
     }
 
+    #[test]
+    fn genres_placeholder_count_limiter_caps_the_list() {
+        let mut info = episode_info();
+        info.genres = vec!["Drama".into(), "Comedy".into(), "Crime".into()];
+        assert_eq!(
+            format_template("{genres:2}", &info, AirDateFormat::Iso),
+            "Drama, Comedy"
+        );
+        // A limit larger than the list just renders everything.
+        assert_eq!(
+            format_template("{genres:10}", &info, AirDateFormat::Iso),
+            "Drama, Comedy, Crime"
+        );
+        // A non-numeric arg falls back to the unlimited rendering.
+        assert_eq!(
+            format_template("{genres:oops}", &info, AirDateFormat::Iso),
+            "Drama, Comedy, Crime"
+        );
+    }
+
     #[test]
     fn se_placeholder_is_zero_padded() {
         let info = episode_info();
-        assert_eq!(format_template("{se}", &info), "S01E02");
+        assert_eq!(format_template("{se}", &info, AirDateFormat::Iso), "S01E02");
     }
 
     #[test]
     fn missing_values_render_empty() {
         let info = MediaInfo::test_stub(MediaType::Movie);
         assert_eq!(
-            format_template("{show}{season}{episode}{year}{se}", &info),
+            format_template(
+                "{show}{season}{episode}{year}{se}",
+                &info,
+                AirDateFormat::Iso
+            ),
             ""
         );
-        assert_eq!(format_template("{artist} - {album}", &info), " - ");
+        assert_eq!(
+            format_template("{artist} - {album}", &info, AirDateFormat::Iso),
+            " - "
+        );
     }
 
     #[test]
     fn unknown_placeholders_are_preserved() {
         let info = episode_info();
-        assert_eq!(format_template("{nope} {title}", &info), "{nope} Pilot");
+        assert_eq!(
+            format_template("{nope} {title}", &info, AirDateFormat::Iso),
+            "{nope} Pilot"
+        );
     }
 
     #[test]
     fn escaped_braces_are_literal() {
         let info = episode_info();
         assert_eq!(
-            format_template("{{title}} = {title}", &info),
+            format_template("{{title}} = {title}", &info, AirDateFormat::Iso),
             "{title} = Pilot"
         );
     }
@@ -187,7 +688,29 @@ mod tests {
     #[test]
     fn unterminated_placeholder_is_preserved() {
         let info = episode_info();
-        assert_eq!(format_template("oops {title", &info), "oops {title");
+        assert_eq!(
+            format_template("oops {title", &info, AirDateFormat::Iso),
+            "oops {title"
+        );
+    }
+
+    #[test]
+    fn unknown_placeholders_flags_typos_but_not_known_names() {
+        assert_eq!(
+            unknown_placeholders("{titel} · {season}"),
+            vec!["titel".to_string()]
+        );
+        assert!(unknown_placeholders("{title} {se}").is_empty());
+    }
+
+    #[test]
+    fn unknown_placeholders_does_not_flag_a_count_limiter_arg_on_a_known_name() {
+        assert!(unknown_placeholders("{genres:3}").is_empty());
+    }
+
+    #[test]
+    fn unknown_placeholders_ignores_escaped_braces_and_unterminated_ones() {
+        assert!(unknown_placeholders("{{literal}} oops {unterminated").is_empty());
     }
 
     #[test]
@@ -212,6 +735,82 @@ mod tests {
         assert!(build_presence(&info, &config).buttons.is_empty());
     }
 
+    #[test]
+    fn live_session_suppresses_timestamps_and_exposes_the_channel_placeholder() {
+        let mut info = episode_info();
+        info.is_live = true;
+        info.channel = Some("NBC".into());
+        let config = Config {
+            timestamp_mode: TimestampMode::Both,
+            tv_state: "{channel}".into(),
+            ..Config::default()
+        };
+        let p = build_presence(&info, &config);
+        assert_eq!(p.state, "NBC");
+        assert_eq!(p.timestamp_mode, TimestampMode::None);
+    }
+
+    #[test]
+    fn server_placeholder_renders_the_source_servers_name() {
+        let info = episode_info();
+        let config = Config {
+            tv_state: "{show} via {server}".into(),
+            ..Config::default()
+        };
+        let p = build_presence(&info, &config);
+        assert_eq!(p.state, "The Show via Test Server");
+    }
+
+    #[test]
+    fn group_session_exposes_the_is_group_and_group_name_placeholders() {
+        let mut info = episode_info();
+        info.is_group = true;
+        info.group_name = Some("bob, carol".into());
+        let config = Config {
+            tv_state: "{is_group}: {group_name}".into(),
+            ..Config::default()
+        };
+        let p = build_presence(&info, &config);
+        assert_eq!(p.state, "Group: bob, carol");
+    }
+
+    #[test]
+    fn solo_session_renders_is_group_and_group_name_as_empty() {
+        let info = episode_info();
+        let config = Config {
+            tv_state: "{is_group}{group_name}".into(),
+            ..Config::default()
+        };
+        let p = build_presence(&info, &config);
+        assert_eq!(p.state, "");
+    }
+
+    #[test]
+    fn group_activity_type_overrides_the_activity_while_is_group_is_set() {
+        let mut info = episode_info();
+        info.is_group = true;
+        let config = Config {
+            group_activity_type: Some(ActivityType::Playing),
+            tv_activity_type: Some(ActivityType::Listening),
+            ..Config::default()
+        };
+        let p = build_presence(&info, &config);
+        assert!(matches!(p.activity_type, ActivityType::Playing));
+    }
+
+    #[test]
+    fn idle_presence_uses_the_configured_templates_without_timestamps() {
+        let config = Config {
+            idle_details: "Browsing Plex".into(),
+            idle_state: "Idle".into(),
+            ..Config::default()
+        };
+        let p = build_idle_presence(&config);
+        assert_eq!(p.details, "Browsing Plex");
+        assert_eq!(p.state, "Idle");
+        assert_eq!(p.timestamp_mode, TimestampMode::None);
+    }
+
     #[test]
     fn tracks_use_listening_activity() {
         let info = MediaInfo::test_stub(MediaType::Track);
@@ -222,16 +821,670 @@ mod tests {
     }
 
     #[test]
-    fn artwork_toggle_falls_back_to_default_image() {
+    fn clips_use_their_own_templates_and_watching_activity() {
+        let mut info = MediaInfo::test_stub(MediaType::Clip);
+        info.title = "Music Video".into();
+        let config = Config {
+            clip_details: "Clip: {title}".into(),
+            ..Config::default()
+        };
+        let p = build_presence(&info, &config);
+        assert_eq!(p.details, "Clip: Music Video");
+        assert!(matches!(p.activity_type, ActivityType::Watching));
+    }
+
+    #[test]
+    fn anime_episodes_use_the_anime_templates_when_configured() {
         let mut info = episode_info();
-        info.art_url = Some("https://img.example/x.jpg".into());
+        info.mal_id = Some("100".into());
+        let config = Config {
+            anime_details: Some("{show} · Episode {episode}".into()),
+            anime_state: Some("{title}".into()),
+            ..Config::default()
+        };
+        let p = build_presence(&info, &config);
+        assert_eq!(p.details, "The Show · Episode 2");
+        assert_eq!(p.state, "Pilot");
+    }
+
+    #[test]
+    fn anime_detection_via_genre_keywords_also_selects_the_anime_templates() {
+        let mut info = episode_info();
+        info.genres = vec!["Anime".into()];
+        let config = Config {
+            anime_details: Some("Anime: {show}".into()),
+            ..Config::default()
+        };
+        let p = build_presence(&info, &config);
+        assert_eq!(p.details, "Anime: The Show");
+    }
+
+    #[test]
+    fn anime_episodes_fall_back_to_the_tv_templates_when_unset() {
+        let mut info = episode_info();
+        info.mal_id = Some("100".into());
         let p = build_presence(&info, &Config::default());
-        assert_eq!(p.large_image.as_deref(), Some("https://img.example/x.jpg"));
+        assert_eq!(p.details, "The Show");
+        assert_eq!(p.state, "S1 · E2 - Pilot");
+    }
+
+    #[test]
+    fn non_anime_episodes_ignore_the_anime_templates() {
+        let info = episode_info();
         let config = Config {
-            show_artwork: false,
+            anime_details: Some("Anime: {show}".into()),
             ..Config::default()
         };
         let p = build_presence(&info, &config);
-        assert_eq!(p.large_image.as_deref(), Some(DEFAULT_IMAGE));
+        assert_eq!(p.details, "The Show");
+    }
+
+    #[test]
+    fn progress_and_remaining_resolve_in_every_field() {
+        let mut info = episode_info();
+        info.view_offset_ms = 65_000;
+        info.duration_ms = 185_000;
+        assert_eq!(
+            format_template("{progress}", &info, AirDateFormat::Iso),
+            "1:05"
+        );
+        assert_eq!(
+            format_template("{remaining}", &info, AirDateFormat::Iso),
+            "2:00"
+        );
+        assert_eq!(
+            format_template("{progress} / {remaining}", &info, AirDateFormat::Iso),
+            "1:05 / 2:00"
+        );
+    }
+
+    #[test]
+    fn percent_placeholder_rounds_to_the_nearest_integer_and_is_empty_without_a_duration() {
+        let mut info = episode_info();
+        info.view_offset_ms = 65_000;
+        info.duration_ms = 185_000;
+        assert_eq!(
+            format_template("{percent}", &info, AirDateFormat::Iso),
+            "35"
+        );
+
+        info.duration_ms = 0;
+        assert_eq!(format_template("{percent}", &info, AirDateFormat::Iso), "");
+    }
+
+    #[test]
+    fn activity_type_override_takes_precedence_over_default() {
+        let config = Config {
+            music_activity_type: Some(ActivityType::Playing),
+            ..Config::default()
+        };
+        let p = build_presence(&MediaInfo::test_stub(MediaType::Track), &config);
+        assert!(matches!(p.activity_type, ActivityType::Playing));
+    }
+
+    #[test]
+    fn timestamp_mode_is_passed_through_from_config() {
+        let config = Config {
+            timestamp_mode: TimestampMode::Elapsed,
+            ..Config::default()
+        };
+        let p = build_presence(&MediaInfo::test_stub(MediaType::Movie), &config);
+        assert_eq!(p.timestamp_mode, TimestampMode::Elapsed);
+    }
+
+    #[test]
+    fn freeze_paused_timestamp_disabled_omits_timestamps_while_paused() {
+        let config = Config {
+            timestamp_mode: TimestampMode::Both,
+            freeze_paused_timestamp: false,
+            ..Config::default()
+        };
+        let mut info = MediaInfo::test_stub(MediaType::Movie);
+        info.state = PlaybackState::Paused;
+        let p = build_presence(&info, &config);
+        assert_eq!(p.timestamp_mode, TimestampMode::None);
+
+        // Still shown as normal while actually playing.
+        info.state = PlaybackState::Playing;
+        let p = build_presence(&info, &config);
+        assert_eq!(p.timestamp_mode, TimestampMode::Both);
+    }
+
+    #[test]
+    fn freeze_paused_timestamp_enabled_by_default_leaves_the_configured_mode() {
+        let config = Config::default();
+        let mut info = MediaInfo::test_stub(MediaType::Movie);
+        info.state = PlaybackState::Paused;
+        let p = build_presence(&info, &config);
+        assert_eq!(p.timestamp_mode, config.timestamp_mode);
+    }
+
+    #[test]
+    fn score_placeholder_prefers_audience_rating_and_formats_one_decimal() {
+        let mut info = MediaInfo::test_stub(MediaType::Movie);
+        info.critic_rating = Some(7.0);
+        info.audience_rating = Some(8.4);
+        assert_eq!(format_template("{score}", &info, AirDateFormat::Iso), "8.4");
+        info.audience_rating = None;
+        assert_eq!(format_template("{score}", &info, AirDateFormat::Iso), "7.0");
+        info.critic_rating = None;
+        assert_eq!(format_template("{score}", &info, AirDateFormat::Iso), "");
+    }
+
+    #[test]
+    fn director_and_studio_placeholders_render() {
+        let mut info = MediaInfo::test_stub(MediaType::Movie);
+        info.directors = vec!["Denis Villeneuve".into(), "Ridley Scott".into()];
+        info.studio = Some("Warner Bros.".into());
+        assert_eq!(
+            format_template("{director} · {studio}", &info, AirDateFormat::Iso),
+            "Denis Villeneuve, Ridley Scott · Warner Bros."
+        );
+    }
+
+    #[test]
+    fn network_placeholder_renders_when_present_and_empty_otherwise() {
+        let mut info = episode_info();
+        info.network = Some("HBO".into());
+        assert_eq!(
+            format_template("Watching {title} on {network}", &info, AirDateFormat::Iso),
+            "Watching Pilot on HBO"
+        );
+        info.network = None;
+        assert_eq!(
+            format_template("on {network}", &info, AirDateFormat::Iso),
+            "on "
+        );
+    }
+
+    #[test]
+    fn rating_placeholder_renders_when_present_and_empty_otherwise() {
+        let mut info = episode_info();
+        info.rating = Some("TV-MA".into());
+        assert_eq!(
+            format_template("{title} · {rating}", &info, AirDateFormat::Iso),
+            "Pilot · TV-MA"
+        );
+        info.rating = None;
+        assert_eq!(format_template("{rating}", &info, AirDateFormat::Iso), "");
+    }
+
+    #[test]
+    fn composer_and_work_placeholders_render_when_present_and_empty_otherwise() {
+        let mut info = MediaInfo::test_stub(MediaType::Track);
+        info.composer = Some("Ludwig van Beethoven".into());
+        info.work = Some("Symphony No. 5".into());
+        assert_eq!(
+            format_template("{work} by {composer}", &info, AirDateFormat::Iso),
+            "Symphony No. 5 by Ludwig van Beethoven"
+        );
+
+        info.composer = None;
+        info.work = None;
+        assert_eq!(
+            format_template("{work} by {composer}", &info, AirDateFormat::Iso),
+            " by "
+        );
+    }
+
+    #[test]
+    fn language_placeholders_render_when_selected_and_empty_otherwise() {
+        let mut info = episode_info();
+        info.sub_lang = Some("Japanese".into());
+        assert_eq!(
+            format_template("Subs: {sub_lang}", &info, AirDateFormat::Iso),
+            "Subs: Japanese"
+        );
+        assert_eq!(
+            format_template("{audio_lang}", &info, AirDateFormat::Iso),
+            ""
+        );
+    }
+
+    #[test]
+    fn playback_method_placeholder_renders_when_known() {
+        let mut info = episode_info();
+        info.playback_method = Some("Transcode".into());
+        assert_eq!(
+            format_template("{playback_method}", &info, AirDateFormat::Iso),
+            "Transcode"
+        );
+        info.playback_method = None;
+        assert_eq!(
+            format_template("{playback_method}", &info, AirDateFormat::Iso),
+            ""
+        );
+    }
+
+    #[test]
+    fn device_placeholder_renders_when_known_and_empty_otherwise() {
+        let mut info = episode_info();
+        info.device = Some("Plex for Apple TV".into());
+        assert_eq!(
+            format_template("{device}", &info, AirDateFormat::Iso),
+            "Plex for Apple TV"
+        );
+        info.device = None;
+        assert_eq!(format_template("{device}", &info, AirDateFormat::Iso), "");
+    }
+
+    #[test]
+    fn track_placeholders_render_position_in_album() {
+        let mut info = MediaInfo::test_stub(MediaType::Track);
+        info.album = Some("Album".into());
+        info.track_number = Some(4);
+        info.track_total = Some(10);
+        assert_eq!(
+            format_template("{track}/{track_total} · {album}", &info, AirDateFormat::Iso),
+            "4/10 · Album"
+        );
+    }
+
+    #[test]
+    fn episode_total_placeholder_renders_when_present_and_empty_otherwise() {
+        let mut info = episode_info();
+        info.episode_total = Some(10);
+        assert_eq!(
+            format_template("E{episode}/{episode_total}", &info, AirDateFormat::Iso),
+            "E2/10"
+        );
+
+        info.episode_total = None;
+        assert_eq!(
+            format_template("E{episode}/{episode_total}", &info, AirDateFormat::Iso),
+            "E2/"
+        );
+    }
+
+    #[test]
+    fn next_title_placeholder_renders_when_present_and_empty_on_the_finale() {
+        let mut info = episode_info();
+        info.next_title = Some("The Next One".into());
+        assert_eq!(
+            format_template("Up next: {next_title}", &info, AirDateFormat::Iso),
+            "Up next: The Next One"
+        );
+
+        info.next_title = None;
+        assert_eq!(
+            format_template("Up next: {next_title}", &info, AirDateFormat::Iso),
+            "Up next: "
+        );
+    }
+
+    #[test]
+    fn air_date_placeholder_renders_iso_or_localized_and_empty_when_absent() {
+        let mut info = episode_info();
+        info.original_air_date = Some("2019-04-14".into());
+        assert_eq!(
+            format_template("Aired {air_date}", &info, AirDateFormat::Iso),
+            "Aired 2019-04-14"
+        );
+        assert_eq!(
+            format_template("Aired {air_date}", &info, AirDateFormat::Localized),
+            "Aired Apr 14, 2019"
+        );
+
+        info.original_air_date = None;
+        assert_eq!(
+            format_template("Aired {air_date}", &info, AirDateFormat::Iso),
+            "Aired "
+        );
+    }
+
+    #[test]
+    fn music_artist_forward_swaps_details_and_state() {
+        let mut info = MediaInfo::test_stub(MediaType::Track);
+        info.title = "Song".into();
+        info.artist = Some("Artist".into());
+        let config = Config::default();
+        let p = build_presence(&info, &config);
+        assert_eq!(p.details, "Song");
+        assert_eq!(p.state, "Artist");
+
+        let config = Config {
+            music_artist_forward: true,
+            ..Config::default()
+        };
+        let p = build_presence(&info, &config);
+        assert_eq!(p.details, "Artist");
+        assert_eq!(p.state, "Song");
+    }
+
+    #[test]
+    fn build_presence_across_media_types() {
+        let movie = {
+            let mut info = MediaInfo::test_stub(MediaType::Movie);
+            info.title = "Dune".into();
+            info.year = Some(2021);
+            info.genres = vec!["Sci-Fi".into()];
+            info
+        };
+        let p = build_presence(&movie, &Config::default());
+        assert_eq!(p.details, "Dune (2021)");
+        assert_eq!(p.state, "Sci-Fi");
+        assert_eq!(p.large_image.as_deref(), Some(DEFAULT_IMAGE));
+        assert!(p.buttons.is_empty());
+        assert!(matches!(p.activity_type, ActivityType::Watching));
+
+        let mut episode = episode_info();
+        episode.imdb_id = Some("tt1".into());
+        let p = build_presence(&episode, &Config::default());
+        assert_eq!(p.details, "The Show");
+        assert_eq!(p.state, "S1 · E2 - Pilot");
+        assert_eq!(p.large_image.as_deref(), Some(DEFAULT_IMAGE));
+        assert_eq!(p.buttons.len(), 1);
+        assert!(p.buttons[0].url.contains("imdb.com/title/tt1"));
+        assert!(matches!(p.activity_type, ActivityType::Watching));
+
+        let mut anime_episode = episode_info();
+        anime_episode.genres = vec!["Anime".into()];
+        anime_episode.mal_id = Some("100".into());
+        let p = build_presence(&anime_episode, &Config::default());
+        assert_eq!(p.buttons.len(), 1);
+        assert!(p.buttons[0].url.contains("myanimelist.net/anime/100"));
+        assert!(matches!(p.activity_type, ActivityType::Watching));
+
+        let mut track = MediaInfo::test_stub(MediaType::Track);
+        track.title = "Song".into();
+        track.artist = Some("Artist".into());
+        track.album = Some("Album".into());
+        let p = build_presence(&track, &Config::default());
+        assert_eq!(p.details, "Song");
+        assert_eq!(p.state, "Artist");
+        assert_eq!(p.large_image_text, "Album");
+        assert!(matches!(p.activity_type, ActivityType::Listening));
+    }
+
+    #[test]
+    fn watching_title_preset_overrides_details_and_activity_type() {
+        let config = Config {
+            watching_title_preset: true,
+            music_activity_type: Some(ActivityType::Playing),
+            ..Config::default()
+        };
+
+        let p = build_presence(&episode_info(), &config);
+        assert_eq!(p.details, "The Show");
+        assert!(matches!(p.activity_type, ActivityType::Watching));
+
+        let mut movie = MediaInfo::test_stub(MediaType::Movie);
+        movie.title = "Dune".into();
+        let p = build_presence(&movie, &config);
+        assert_eq!(p.details, "Dune");
+        assert!(matches!(p.activity_type, ActivityType::Watching));
+
+        let mut track = MediaInfo::test_stub(MediaType::Track);
+        track.title = "Song".into();
+        let p = build_presence(&track, &config);
+        assert_eq!(p.details, "Song");
+        assert!(matches!(p.activity_type, ActivityType::Watching));
+    }
+
+    #[test]
+    fn artwork_toggle_falls_back_to_default_image() {
+        let mut info = episode_info();
+        info.art_url = Some("https://img.example/x.jpg".into());
+        let p = build_presence(&info, &Config::default());
+        assert_eq!(p.large_image.as_deref(), Some("https://img.example/x.jpg"));
+        let config = Config {
+            show_artwork: false,
+            ..Config::default()
+        };
+        let p = build_presence(&info, &config);
+        assert_eq!(p.large_image.as_deref(), Some(DEFAULT_IMAGE));
+    }
+
+    #[test]
+    fn clean_movie_title_strips_a_trailing_parenthetical() {
+        assert_eq!(
+            clean_movie_title("Blade Runner (Director's Cut)"),
+            "Blade Runner"
+        );
+        assert_eq!(clean_movie_title("Dune (2021)"), "Dune");
+        assert_eq!(clean_movie_title("Dune"), "Dune");
+    }
+
+    #[test]
+    fn clean_movie_titles_config_flag_only_affects_movies() {
+        let config = Config {
+            clean_movie_titles: true,
+            movie_details: "{title}".into(),
+            tv_details: "{title}".into(),
+            ..Config::default()
+        };
+
+        let mut movie = MediaInfo::test_stub(MediaType::Movie);
+        movie.title = "Dune (2021)".into();
+        let p = build_presence(&movie, &config);
+        assert_eq!(p.details, "Dune");
+
+        let mut episode = episode_info();
+        episode.title = "Pilot (Extended)".into();
+        let p = build_presence(&episode, &config);
+        assert_eq!(p.details, "Pilot (Extended)");
+    }
+
+    #[test]
+    fn episode_title_fallback_fills_in_a_blank_episode_title_when_enabled() {
+        let config = Config {
+            episode_title_fallback: true,
+            tv_state: "{title}".into(),
+            ..Config::default()
+        };
+        let mut episode = episode_info();
+        episode.title = String::new();
+        episode.episode = Some(3);
+        let p = build_presence(&episode, &config);
+        assert_eq!(p.state, "Episode 3");
+    }
+
+    #[test]
+    fn episode_title_fallback_leaves_a_blank_title_alone_when_disabled() {
+        let config = Config {
+            episode_title_fallback: false,
+            tv_state: "{title}".into(),
+            ..Config::default()
+        };
+        let mut episode = episode_info();
+        episode.title = String::new();
+        let p = build_presence(&episode, &config);
+        assert_eq!(p.state, "");
+    }
+
+    #[test]
+    fn truncate_field_is_grapheme_aware_and_leaves_short_text_alone() {
+        assert_eq!(truncate_field("hello".into(), Some(10)), "hello");
+        assert_eq!(truncate_field("hello world".into(), Some(5)), "hell…");
+        // Each flag emoji is a multi-codepoint grapheme cluster; byte/char
+        // slicing would split it and produce invalid output.
+        let flags = "🇯🇵".repeat(5);
+        assert_eq!(truncate_field(flags, Some(3)), "🇯🇵🇯🇵…");
+    }
+
+    #[test]
+    fn template_max_len_caps_details_state_and_image_text() {
+        let config = Config {
+            template_max_len: Some(5),
+            tv_details: "{show}".into(),
+            tv_state: "{title}".into(),
+            tv_image_text: "{title}".into(),
+            ..Config::default()
+        };
+        let p = build_presence(&episode_info(), &config);
+        assert_eq!(p.details, "The …");
+        assert_eq!(p.state, "Pilot");
+        assert_eq!(p.large_image_text, "Pilot");
+    }
+
+    #[test]
+    fn empty_field_placeholder_substitutes_a_blank_rendered_field() {
+        let config = Config {
+            movie_state: "{genres}".into(),
+            empty_field_placeholder: Some("—".into()),
+            ..Config::default()
+        };
+        let movie = MediaInfo::test_stub(MediaType::Movie);
+        let p = build_presence(&movie, &config);
+        assert_eq!(p.state, "—");
+    }
+
+    #[test]
+    fn empty_field_placeholder_leaves_a_blank_field_alone_when_unconfigured() {
+        let config = Config {
+            movie_state: "{genres}".into(),
+            empty_field_placeholder: None,
+            ..Config::default()
+        };
+        let movie = MediaInfo::test_stub(MediaType::Movie);
+        let p = build_presence(&movie, &config);
+        assert_eq!(p.state, "");
+    }
+
+    #[test]
+    fn button_sources_control_order_and_which_services_appear() {
+        let mut info = episode_info();
+        info.mal_id = Some("100".into());
+        info.imdb_id = Some("tt1".into());
+        info.tmdb_id = Some("200".into());
+
+        let config = Config {
+            button_sources: vec![ButtonSource::Tmdb, ButtonSource::Custom],
+            ..Config::default()
+        };
+        let p = build_presence(&info, &config);
+        assert_eq!(p.buttons.len(), 1);
+        assert!(p.buttons[0].url.contains("themoviedb.org/tv/200"));
+    }
+
+    #[test]
+    fn button_sources_skip_a_source_with_no_id_and_stop_at_the_cap() {
+        let mut info = episode_info();
+        info.imdb_id = Some("tt1".into());
+        info.tmdb_id = Some("200".into());
+
+        let config = Config {
+            button_sources: vec![
+                ButtonSource::Mal,
+                ButtonSource::Imdb,
+                ButtonSource::Tmdb,
+                ButtonSource::Trakt,
+            ],
+            ..Config::default()
+        };
+        let p = build_presence(&info, &config);
+        assert_eq!(p.buttons.len(), 2);
+        assert!(p.buttons[0].url.contains("imdb.com/title/tt1"));
+        assert!(p.buttons[1].url.contains("themoviedb.org/tv/200"));
+    }
+
+    #[test]
+    fn small_image_for_genres_picks_first_matching_genre_case_insensitively() {
+        let mut map = HashMap::new();
+        map.insert("Horror".to_string(), "horror_badge".to_string());
+        let genres = vec!["Comedy".to_string(), "horror".to_string()];
+        assert_eq!(small_image_for_genres(&genres, &map), Some("horror_badge"));
+        assert_eq!(small_image_for_genres(&[], &map), None);
+    }
+
+    #[test]
+    fn build_presence_resolves_small_image_key_from_genre_map() {
+        let mut map = HashMap::new();
+        map.insert("Drama".to_string(), "drama_badge".to_string());
+        let config = Config {
+            genre_small_images: map,
+            ..Config::default()
+        };
+
+        let info = episode_info();
+        let p = build_presence(&info, &config);
+        assert_eq!(p.small_image_key.as_deref(), Some("drama_badge"));
+
+        let p = build_presence(&MediaInfo::test_stub(MediaType::Movie), &config);
+        assert_eq!(p.small_image_key, None);
+    }
+
+    #[test]
+    fn offline_artwork_requires_both_the_dir_and_the_proxy_base_url() {
+        let info = MediaInfo::test_stub(MediaType::Movie);
+        let config = Config {
+            offline_artwork_dir: Some("/opt/art".to_string()),
+            ..Config::default()
+        };
+        assert_eq!(offline_artwork_url(&info, &config), None);
+
+        let config = Config {
+            art_proxy_public_base_url: Some("https://tunnel.example.com".to_string()),
+            ..Config::default()
+        };
+        assert_eq!(offline_artwork_url(&info, &config), None);
+    }
+
+    #[test]
+    fn offline_artwork_falls_back_to_the_media_type_filename() {
+        let info = MediaInfo::test_stub(MediaType::Movie);
+        let config = Config {
+            offline_artwork_dir: Some("/opt/art".to_string()),
+            art_proxy_public_base_url: Some("https://tunnel.example.com".to_string()),
+            ..Config::default()
+        };
+        assert_eq!(
+            offline_artwork_url(&info, &config).as_deref(),
+            Some("https://tunnel.example.com/offline?name=movie%2Epng")
+        );
+    }
+
+    #[test]
+    fn offline_artwork_prefers_a_genre_match_over_the_media_type_filename() {
+        let mut info = MediaInfo::test_stub(MediaType::Episode);
+        info.genres = vec!["Horror".to_string()];
+        let mut map = HashMap::new();
+        map.insert("Horror".to_string(), "horror.png".to_string());
+        let config = Config {
+            offline_artwork_dir: Some("/opt/art".to_string()),
+            art_proxy_public_base_url: Some("https://tunnel.example.com".to_string()),
+            genre_offline_artwork: map,
+            ..Config::default()
+        };
+        assert_eq!(
+            offline_artwork_url(&info, &config).as_deref(),
+            Some("https://tunnel.example.com/offline?name=horror%2Epng")
+        );
+    }
+
+    #[test]
+    fn build_presence_uses_offline_artwork_when_art_url_is_missing() {
+        let info = episode_info();
+        let config = Config {
+            offline_artwork_dir: Some("/opt/art".to_string()),
+            art_proxy_public_base_url: Some("https://tunnel.example.com".to_string()),
+            ..Config::default()
+        };
+        let p = build_presence(&info, &config);
+        assert_eq!(
+            p.large_image.as_deref(),
+            Some("https://tunnel.example.com/offline?name=episode%2Epng")
+        );
+    }
+
+    #[test]
+    fn per_media_type_artwork_flag_overrides_the_global_default() {
+        let mut track = MediaInfo::test_stub(MediaType::Track);
+        track.art_url = Some("https://img.example/album.jpg".into());
+        let config = Config {
+            show_artwork: true,
+            show_artwork_music: Some(false),
+            ..Config::default()
+        };
+        let p = build_presence(&track, &config);
+        assert_eq!(p.large_image.as_deref(), Some(DEFAULT_IMAGE));
+
+        let mut episode = episode_info();
+        episode.art_url = Some("https://img.example/poster.jpg".into());
+        let p = build_presence(&episode, &config);
+        assert_eq!(
+            p.large_image.as_deref(),
+            Some("https://img.example/poster.jpg")
+        );
     }
 }
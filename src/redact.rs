@@ -0,0 +1,70 @@
+/// Masks the value following any `token`-containing key — query-string
+/// `key=value`, JSON `"key":"value"`, header-style `Key: value` — so logs
+/// can be pasted into a GitHub issue without leaking a Plex access token.
+pub fn redact(input: &str) -> String {
+    let lower = input.to_ascii_lowercase();
+    let mut out = String::with_capacity(input.len());
+    let mut pos = 0;
+
+    while let Some(found) = lower[pos..].find("token") {
+        let key_end = pos + found + "token".len();
+        out.push_str(&input[pos..key_end]);
+
+        let after_key = &input[key_end..];
+        let sep_len = after_key
+            .char_indices()
+            .take_while(|(_, c)| matches!(c, '"' | '\'' | ':' | '=' | ' '))
+            .count();
+        if sep_len == 0 || !after_key[..sep_len].contains(['=', ':']) {
+            pos = key_end;
+            continue;
+        }
+        let value_start = key_end + sep_len;
+        out.push_str(&input[key_end..value_start]);
+
+        let value_end = input[value_start..]
+            .find(['"', '\'', '&', ' ', ',', '}'])
+            .map(|n| value_start + n)
+            .unwrap_or(input.len());
+        if value_end > value_start {
+            out.push_str("***");
+        }
+        pos = value_end;
+    }
+    out.push_str(&input[pos..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_query_string_token() {
+        assert_eq!(
+            redact("https://host/:/eventsource?X-Plex-Token=abc123&filters=playing"),
+            "https://host/:/eventsource?X-Plex-Token=***&filters=playing"
+        );
+    }
+
+    #[test]
+    fn redacts_json_token_field() {
+        assert_eq!(
+            redact(r#"{"authToken": "abc123", "state": "playing"}"#),
+            r#"{"authToken": "***", "state": "playing"}"#
+        );
+    }
+
+    #[test]
+    fn leaves_text_without_a_token_unchanged() {
+        assert_eq!(
+            redact("SSE connected: http://host:32400"),
+            "SSE connected: http://host:32400"
+        );
+    }
+
+    #[test]
+    fn does_not_misfire_on_unrelated_words_containing_token() {
+        assert_eq!(redact("tokenized request"), "tokenized request");
+    }
+}
@@ -4,10 +4,11 @@ use std::time::Duration;
 use tokio::sync::mpsc::UnboundedSender;
 use tray_icon::{
     Icon, TrayIconBuilder,
-    menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem},
+    menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem},
 };
 
-use crate::media::PlaybackState;
+use crate::media::{MediaType, PlaybackState};
+use crate::metadata::TmdbHealth;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TrayStatus {
@@ -16,6 +17,14 @@ pub enum TrayStatus {
     Paused,
     Buffering,
     NotAuthenticated,
+    Authenticating,
+    ServerUnreachable,
+    TmdbUnavailable,
+    // The Plex token was rejected outright (401), e.g. revoked from
+    // plex.tv/devices, rather than just unset. Distinct from
+    // `NotAuthenticated` so troubleshooting can tell "never logged in" apart
+    // from "was logged in, but Plex no longer accepts it".
+    ReauthRequired,
 }
 
 impl TrayStatus {
@@ -26,6 +35,10 @@ impl TrayStatus {
             Self::Paused => "Status: Paused",
             Self::Buffering => "Status: Buffering",
             Self::NotAuthenticated => "Status: Not Authenticated",
+            Self::Authenticating => "Status: Authenticating",
+            Self::ServerUnreachable => "Status: Server Unreachable",
+            Self::TmdbUnavailable => "Status: TMDB Unavailable",
+            Self::ReauthRequired => "Status: Re-authentication Required",
         }
     }
 }
@@ -40,16 +53,33 @@ impl From<PlaybackState> for TrayStatus {
     }
 }
 
+impl From<TmdbHealth> for TrayStatus {
+    fn from(_: TmdbHealth) -> Self {
+        // Both failure modes mean the same thing to a user looking at the
+        // tray: artwork lookups aren't working right now.
+        Self::TmdbUnavailable
+    }
+}
+
 #[derive(Debug)]
 pub enum TrayCommand {
     Quit,
     Authenticate,
+    ToggleMovies,
+    ToggleTvShows,
+    ToggleMusic,
+    TogglePause,
+    OpenLog,
+    OpenConfigFolder,
 }
 
 #[cfg(target_os = "linux")]
 enum MenuTextUpdate {
     Status(String),
     Auth(String),
+    Tooltip(String),
+    Pause(String),
+    Checked(MediaType, bool),
 }
 
 pub struct TrayHandle {
@@ -59,6 +89,14 @@ pub struct TrayHandle {
     status_item: MenuItem,
     #[cfg(not(target_os = "linux"))]
     auth_item: MenuItem,
+    #[cfg(not(target_os = "linux"))]
+    pause_item: MenuItem,
+    #[cfg(not(target_os = "linux"))]
+    movies_item: CheckMenuItem,
+    #[cfg(not(target_os = "linux"))]
+    tv_item: CheckMenuItem,
+    #[cfg(not(target_os = "linux"))]
+    music_item: CheckMenuItem,
     #[cfg(target_os = "linux")]
     update_tx: std::sync::mpsc::Sender<MenuTextUpdate>,
 }
@@ -68,9 +106,16 @@ impl TrayHandle {
         #[cfg(not(target_os = "linux"))]
         self.status_item.set_text(text);
         #[cfg(target_os = "linux")]
-        let _ = self
-            .update_tx
-            .send(MenuTextUpdate::Status(text.to_string()));
+        {
+            let _ = self
+                .update_tx
+                .send(MenuTextUpdate::Status(text.to_string()));
+            // GNOME+Wayland never shows the menu unless it's opened, so the
+            // tooltip is the only always-visible place status shows up.
+            let _ = self
+                .update_tx
+                .send(MenuTextUpdate::Tooltip(text.to_string()));
+        }
     }
 
     pub fn set_auth_text(&self, text: &str) {
@@ -79,12 +124,50 @@ impl TrayHandle {
         #[cfg(target_os = "linux")]
         let _ = self.update_tx.send(MenuTextUpdate::Auth(text.to_string()));
     }
+
+    pub fn set_pause_text(&self, text: &str) {
+        #[cfg(not(target_os = "linux"))]
+        self.pause_item.set_text(text);
+        #[cfg(target_os = "linux")]
+        let _ = self.update_tx.send(MenuTextUpdate::Pause(text.to_string()));
+    }
+
+    pub fn set_checked(&self, media_type: MediaType, checked: bool) {
+        #[cfg(not(target_os = "linux"))]
+        {
+            let item = match media_type {
+                MediaType::Movie => &self.movies_item,
+                MediaType::Episode => &self.tv_item,
+                MediaType::Track => &self.music_item,
+                // Clips have no dedicated tray checkbox.
+                MediaType::Clip => return,
+            };
+            item.set_checked(checked);
+        }
+        #[cfg(target_os = "linux")]
+        let _ = self
+            .update_tx
+            .send(MenuTextUpdate::Checked(media_type, checked));
+    }
+}
+
+struct TrayParts {
+    status_item: MenuItem,
+    auth_item: MenuItem,
+    pause_item: MenuItem,
+    movies_item: CheckMenuItem,
+    tv_item: CheckMenuItem,
+    music_item: CheckMenuItem,
+    tray: tray_icon::TrayIcon,
 }
 
 fn build_tray(
     tx: UnboundedSender<TrayCommand>,
     authenticated: bool,
-) -> Option<(MenuItem, MenuItem, tray_icon::TrayIcon)> {
+    enable_movies: bool,
+    enable_tv_shows: bool,
+    enable_music: bool,
+) -> Option<TrayParts> {
     let menu = Menu::new();
     let status_item = MenuItem::new(
         if authenticated {
@@ -105,11 +188,24 @@ fn build_tray(
         true,
         None,
     );
+    let pause_item = MenuItem::new("Pause presence", true, None);
+    let movies_item = CheckMenuItem::new("Movies", true, enable_movies, None);
+    let tv_item = CheckMenuItem::new("TV Shows", true, enable_tv_shows, None);
+    let music_item = CheckMenuItem::new("Music", true, enable_music, None);
+    let open_log_item = MenuItem::new("Open Log", true, None);
+    let open_config_item = MenuItem::new("Open Config Folder", true, None);
     let quit_item = MenuItem::new("Quit", true, None);
 
     menu.append(&status_item).ok()?;
     menu.append(&PredefinedMenuItem::separator()).ok()?;
+    menu.append(&movies_item).ok()?;
+    menu.append(&tv_item).ok()?;
+    menu.append(&music_item).ok()?;
+    menu.append(&pause_item).ok()?;
+    menu.append(&PredefinedMenuItem::separator()).ok()?;
     menu.append(&auth_item).ok()?;
+    menu.append(&open_log_item).ok()?;
+    menu.append(&open_config_item).ok()?;
     menu.append(&quit_item).ok()?;
 
     let img = image::load_from_memory(include_bytes!("../assets/icon.ico")).ok()?;
@@ -124,6 +220,13 @@ fn build_tray(
         .ok()?;
 
     let (auth_id, quit_id) = (auth_item.id().clone(), quit_item.id().clone());
+    let pause_id = pause_item.id().clone();
+    let (movies_id, tv_id, music_id) = (
+        movies_item.id().clone(),
+        tv_item.id().clone(),
+        music_item.id().clone(),
+    );
+    let (open_log_id, open_config_id) = (open_log_item.id().clone(), open_config_item.id().clone());
 
     std::thread::spawn(move || {
         let recv = MenuEvent::receiver();
@@ -136,6 +239,24 @@ fn build_tray(
                 Ok(e) if e.id == auth_id => {
                     let _ = tx.send(TrayCommand::Authenticate);
                 }
+                Ok(e) if e.id == pause_id => {
+                    let _ = tx.send(TrayCommand::TogglePause);
+                }
+                Ok(e) if e.id == movies_id => {
+                    let _ = tx.send(TrayCommand::ToggleMovies);
+                }
+                Ok(e) if e.id == tv_id => {
+                    let _ = tx.send(TrayCommand::ToggleTvShows);
+                }
+                Ok(e) if e.id == music_id => {
+                    let _ = tx.send(TrayCommand::ToggleMusic);
+                }
+                Ok(e) if e.id == open_log_id => {
+                    let _ = tx.send(TrayCommand::OpenLog);
+                }
+                Ok(e) if e.id == open_config_id => {
+                    let _ = tx.send(TrayCommand::OpenConfigFolder);
+                }
                 Ok(_) => {}
                 Err(RecvTimeoutError::Timeout) if tx.is_closed() => break,
                 Err(RecvTimeoutError::Disconnected) => break,
@@ -144,11 +265,25 @@ fn build_tray(
         }
     });
 
-    Some((status_item, auth_item, tray))
+    Some(TrayParts {
+        status_item,
+        auth_item,
+        pause_item,
+        movies_item,
+        tv_item,
+        music_item,
+        tray,
+    })
 }
 
 #[cfg(target_os = "linux")]
-pub fn setup(tx: UnboundedSender<TrayCommand>, authenticated: bool) -> Option<TrayHandle> {
+pub fn setup(
+    tx: UnboundedSender<TrayCommand>,
+    authenticated: bool,
+    enable_movies: bool,
+    enable_tv_shows: bool,
+    enable_music: bool,
+) -> Option<TrayHandle> {
     let (ready_tx, ready_rx) = std::sync::mpsc::sync_channel(1);
     let (update_tx, update_rx) = std::sync::mpsc::channel::<MenuTextUpdate>();
     std::thread::spawn(move || {
@@ -157,22 +292,46 @@ pub fn setup(tx: UnboundedSender<TrayCommand>, authenticated: bool) -> Option<Tr
             ready_tx.send(false).ok();
             return;
         }
-        let result = build_tray(tx, authenticated);
+        let result = build_tray(
+            tx,
+            authenticated,
+            enable_movies,
+            enable_tv_shows,
+            enable_music,
+        );
         if result.is_none() {
             ready_tx.send(false).ok();
             return;
         }
-        let (status_item, auth_item, _tray) = result.unwrap();
+        let parts = result.unwrap();
         ready_tx.send(true).ok();
 
-        // Menu items may only be touched from the GTK thread
-        let status = status_item.clone();
-        let auth = auth_item.clone();
+        // Menu items and the tray icon may only be touched from the GTK thread
+        let status = parts.status_item.clone();
+        let auth = parts.auth_item.clone();
+        let pause = parts.pause_item.clone();
+        let movies = parts.movies_item.clone();
+        let tv = parts.tv_item.clone();
+        let music = parts.music_item.clone();
+        let tray = parts.tray;
         gtk::glib::timeout_add_local(Duration::from_millis(50), move || {
             while let Ok(update) = update_rx.try_recv() {
                 match update {
                     MenuTextUpdate::Status(text) => status.set_text(&text),
                     MenuTextUpdate::Auth(text) => auth.set_text(&text),
+                    MenuTextUpdate::Pause(text) => pause.set_text(&text),
+                    MenuTextUpdate::Tooltip(text) => {
+                        let _ = tray.set_tooltip(Some(&text));
+                    }
+                    MenuTextUpdate::Checked(media_type, checked) => {
+                        let item = match media_type {
+                            MediaType::Movie => &movies,
+                            MediaType::Episode => &tv,
+                            MediaType::Track => &music,
+                            MediaType::Clip => continue,
+                        };
+                        item.set_checked(checked);
+                    }
                 }
             }
             gtk::glib::ControlFlow::Continue
@@ -186,12 +345,32 @@ pub fn setup(tx: UnboundedSender<TrayCommand>, authenticated: bool) -> Option<Tr
     Some(TrayHandle { update_tx })
 }
 
+// On Windows, this can run on any thread as long as that thread also
+// drives the win32 event loop. On macOS, tray_icon requires both to happen
+// on the process's real main thread, which main.rs handles by calling this
+// before handing the rest of the app off to a background thread.
 #[cfg(not(target_os = "linux"))]
-pub fn setup(tx: UnboundedSender<TrayCommand>, authenticated: bool) -> Option<TrayHandle> {
-    let (status_item, auth_item, tray) = build_tray(tx, authenticated)?;
+pub fn setup(
+    tx: UnboundedSender<TrayCommand>,
+    authenticated: bool,
+    enable_movies: bool,
+    enable_tv_shows: bool,
+    enable_music: bool,
+) -> Option<TrayHandle> {
+    let parts = build_tray(
+        tx,
+        authenticated,
+        enable_movies,
+        enable_tv_shows,
+        enable_music,
+    )?;
     Some(TrayHandle {
-        _tray: tray,
-        status_item,
-        auth_item,
+        _tray: parts.tray,
+        status_item: parts.status_item,
+        auth_item: parts.auth_item,
+        pause_item: parts.pause_item,
+        movies_item: parts.movies_item,
+        tv_item: parts.tv_item,
+        music_item: parts.music_item,
     })
 }